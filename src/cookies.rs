@@ -0,0 +1,67 @@
+//! Taking turns between several cookie files, so no one account gets rate limited.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long to avoid a cookie file after it was rate limited.
+const COOLDOWN: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Default)]
+pub struct CookieFiles {
+    files:   Vec<PathBuf>,
+    /// The index of the file to try next.
+    next:    usize,
+    /// When each file was last rate limited.
+    limited: HashMap<PathBuf, Instant>,
+}
+
+impl CookieFiles {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self {
+            files,
+            ..Default::default()
+        }
+    }
+
+    /// The file to use for the next download, in turn, skipping the ones that were
+    /// rate limited recently. If they all were, the one that was the longest ago.
+    pub fn next(&mut self) -> Option<PathBuf> {
+        let len = self.files.len();
+        for i in 0..len {
+            let index = (self.next + i) % len;
+            let file = &self.files[index];
+            if self
+                .limited
+                .get(file)
+                .is_none_or(|x| x.elapsed() >= COOLDOWN)
+            {
+                self.next = (index + 1) % len;
+                return Some(file.clone());
+            }
+        }
+        self.files
+            .iter()
+            .min_by_key(|x| self.limited.get(*x))
+            .cloned()
+    }
+
+    /// Avoid `file` for a while.
+    pub fn rate_limited(&mut self, file: &Path) {
+        self.limited.insert(file.to_path_buf(), Instant::now());
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Keep avoiding the files `old` was, e.g. when the config is reloaded with
+    /// different files.
+    pub fn keep_cooldowns(&mut self, old: &CookieFiles) {
+        for (file, at) in &old.limited {
+            if self.files.contains(file) {
+                self.limited.insert(file.clone(), *at);
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Errors from getting info and downloading, so callers can tell them apart. Everything
+//! else uses `eyre`.
+
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// yt-dlp kept failing with what looked like network errors.
+    Network(String),
+    /// yt-dlp exited with a nonzero code. `stderr` is the end of its output.
+    YtDlpFailure {
+        code:   i32,
+        stderr: String,
+    },
+    /// The `pipe_to` command a download was piped to exited with a nonzero code.
+    /// `stderr` is the end of its output.
+    PipeFailure {
+        code:   i32,
+        stderr: String,
+    },
+    /// yt-dlp was killed, e.g. because the download was cancelled or we're shutting
+    /// down.
+    Killed {
+        signal: i32,
+    },
+    /// There's nothing at the URL, e.g. the channel isn't live.
+    NotLive {
+        url: String,
+    },
+    /// The video is only for the channel's members, and the cookies aren't for one.
+    MembersOnly {
+        url: String,
+    },
+    /// The download was stopped for reaching `max_filesize`. What it got before then
+    /// is `kept`.
+    SizeLimit {
+        limit: String,
+        kept:  Option<PathBuf>,
+    },
+    /// The video was deliberately not downloaded, e.g. for being too long.
+    Skipped(String),
+    /// The download finished, but ffprobe found problems with the file.
+    Unverified(String),
+    /// yt-dlp printed something we couldn't make sense of.
+    Parse(String),
+    Io(std::io::Error),
+    /// Reading or writing `path` failed.
+    File {
+        path:   PathBuf,
+        source: std::io::Error,
+    },
+    /// A finished download couldn't be moved into place.
+    Move {
+        from:   PathBuf,
+        to:     PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Network(e) => write!(f, "Network error: {e}"),
+            Error::YtDlpFailure { code, stderr } => {
+                write!(f, "yt-dlp exited with code {code}:\n{stderr}")
+            }
+            Error::PipeFailure { code, stderr } => {
+                write!(f, "pipe_to command exited with code {code}:\n{stderr}")
+            }
+            Error::Killed { signal } => write!(f, "yt-dlp was killed by signal {signal}"),
+            Error::NotLive { url } => write!(f, "No info found for {url}"),
+            Error::MembersOnly { url } => write!(
+                f,
+                "{url} is members-only; set cookies_from_browser or cookies_files to an \
+                 account with a membership"
+            ),
+            Error::SizeLimit { limit, kept } => {
+                write!(f, "Stopped: size limit of {limit} reached")?;
+                match kept {
+                    Some(path) => write!(f, "; what was downloaded was kept at {path:?}"),
+                    None => Ok(()),
+                }
+            }
+            Error::Skipped(reason) => write!(f, "Skipped: {reason}"),
+            Error::Unverified(e) => write!(f, "Verification failed: {e}"),
+            Error::Parse(e) => write!(f, "{e}"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::File { path, source } => write!(f, "{path:?}: {source}"),
+            Error::Move { from, to, source } => write!(f, "{source}: {from:?} -> {to:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e)
+            | Error::File { source: e, .. }
+            | Error::Move { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
@@ -0,0 +1,26 @@
+//! Events about downloads, streamed to IPC clients as they happen.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+static SUBSCRIBERS: Mutex<Vec<Sender<String>>> = Mutex::new(Vec::new());
+
+/// Receive every event sent from now on, as a line of JSON.
+pub fn subscribe() -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
+/// Send `event` to every subscriber, forgetting the ones that have gone away.
+pub fn send(event: &impl Serialize) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    if subscribers.is_empty() {
+        return;
+    }
+    let Ok(json) = serde_json::to_string(event) else {
+        return;
+    };
+    subscribers.retain(|x| x.send(json.clone()).is_ok());
+}
@@ -0,0 +1,94 @@
+//! The server's recent log lines, kept so they can be fetched over IPC.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+
+/// How many lines to keep.
+const CAPACITY: usize = 1000;
+
+static LINES: Mutex<VecDeque<Record>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Info => "Info",
+            Level::Warn => "Warning",
+            Level::Error => "Error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    pub level: Level,
+    pub line:  String,
+}
+
+/// Info lines as they are, others prefixed with their level.
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            Level::Info => f.write_str(&self.line),
+            level => write!(f, "{level}: {}", self.line),
+        }
+    }
+}
+
+/// Keep `line`, forgetting the oldest one if there are too many.
+pub fn record(level: Level, line: String) {
+    let mut lines = LINES.lock().unwrap();
+    if lines.len() == CAPACITY {
+        lines.pop_front();
+    }
+    lines.push_back(Record { level, line });
+}
+
+/// Print `line` to stderr, and keep it.
+pub fn print(level: Level, line: String) {
+    eprintln!(
+        "{}",
+        Record {
+            level,
+            line: line.clone()
+        }
+    );
+    record(level, line);
+}
+
+/// The last `n` lines, oldest first.
+pub fn tail(n: usize) -> Vec<Record> {
+    let lines = LINES.lock().unwrap();
+    lines
+        .iter()
+        .skip(lines.len().saturating_sub(n))
+        .cloned()
+        .collect()
+}
+
+/// Like `eprintln!`, but the line is also kept for `tail`. It's at the info level,
+/// unless it starts with one of `info`, `warn` or `error`, e.g. `log!(warn, ...)`.
+macro_rules! log {
+    (info, $($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Info, format!($($arg)*))
+    };
+    (warn, $($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Warn, format!($($arg)*))
+    };
+    (error, $($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Error, format!($($arg)*))
+    };
+    ($($arg:tt)*) => {
+        $crate::log::print($crate::log::Level::Info, format!($($arg)*))
+    };
+}
+pub(crate) use log;
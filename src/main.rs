@@ -2,79 +2,538 @@ use clap::{Parser, Subcommand};
 use eyre::eyre;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
-use signal_hook::consts::{SIGHUP, SIGTERM};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1};
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, File, OpenOptions};
-use std::io::{Read, Write};
-use std::net::Shutdown;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
 use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::ExitStatusExt;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+mod cookies;
+mod error;
+mod events;
+mod log;
+mod notify;
+mod paths;
+mod schedule;
+#[cfg(test)]
+mod tests;
 #[allow(dead_code)]
 mod yt_dlp;
 
+use cookies::CookieFiles;
+use log::log;
+use notify::Notifier;
 use yt_dlp::*;
 
 const NAME: &'static str = env!("CARGO_PKG_NAME");
 
 #[derive(Debug)]
 struct Watching {
-    thread: std::thread::JoinHandle<eyre::Result<()>>,
-    info:   Info,
-    dl_dir: PathBuf,
+    thread:       std::thread::JoinHandle<eyre::Result<()>>,
+    info:         Info,
+    dl_dir:       PathBuf,
+    status:       Arc<DlStatus>,
+    /// How many bytes have been downloaded, and when that last changed.
+    progress:     (u64, Instant),
+    /// When `download_timeout_secs` counts from: when it started, or for live streams,
+    /// when the stream was seen to have ended.
+    timeout_from: Option<Instant>,
+    /// When it was stopped for going over `download_timeout_secs`.
+    timed_out:    Option<Instant>,
 }
 
 impl Watching {
-    fn watch(mut yt_dlp: YtDlp, id: &Id) -> eyre::Result<Self> {
-        let dl_dir = dirs::cache_dir()
-            .expect("cache dir")
-            .join(NAME)
-            .join(id.to_string());
-        let info = Info::get(&yt_dlp, &id)?;
+    /// Start downloading `id`, found through `subscription` if that's not `id` itself.
+    /// `running` is how many other downloads there are, to share `total_limit_rate`
+    /// between.
+    fn watch(
+        mut yt_dlp: YtDlp,
+        options: DlOptions,
+        id: &Id,
+        subscription: Option<&Id>,
+        running: usize,
+    ) -> eyre::Result<Self> {
+        if let Id::Playlist { .. } = id {
+            return Err(eyre!(
+                "{id} is a playlist; its entries are downloaded instead"
+            ));
+        }
+        id.validate_dir_name()?;
+        let dl_dir = paths::cache_dir().join(id.to_string());
+        options.next_cookies(&mut yt_dlp);
+        let mut info = Info::get(&yt_dlp, &id)
+            .inspect_err(|e| options.note_rate_limit(&yt_dlp, &e.to_string()))?;
+        info.started_at = Some(unix_now());
+        info.subscription = subscription.cloned();
+        let status = Arc::new(DlStatus::default());
+        if !yt_dlp.age_allowed(info.age_limit) {
+            // Finish straight away, so it's reported and remembered like any other
+            // download instead of being checked again on every poll.
+            let error = error::Error::Skipped(format!(
+                "rated {}+, above the age limit of {}",
+                info.age_limit.unwrap_or_default(),
+                yt_dlp.age_limit.unwrap_or_default()
+            ));
+            return Ok(Self {
+                thread: std::thread::spawn(move || Err(error.into())),
+                info,
+                dl_dir,
+                status,
+                progress: (0, Instant::now()),
+                timeout_from: None,
+                timed_out: None,
+            });
+        }
+        if let Some(total) = options.total_limit_rate {
+            yt_dlp.limit_rate(Some(total / (running as u64 + 1)));
+        }
+        if info.is_live()
+            && let Some(n) = options.live_fragments
+        {
+            yt_dlp.concurrent_fragments(Some(n));
+        }
+        if let Some(format) = &options.remux_video {
+            yt_dlp.remux_video(Some(format.as_str()).filter(|x| *x != "none"));
+        }
+        yt_dlp
+            .no_progress(false)
+            .live_from_start(options.live_from_start_for(id))
+            .wait_for_video(options.wait_for_video.as_deref());
         let thread = match id {
             Id::Twitch { twitch_id } => {
                 let t = std::thread::spawn({
                     let twitch_id = twitch_id.clone();
+                    let info = info.clone();
                     let dl_dir = dl_dir.clone();
-                    move || twitch_dl(&yt_dlp, &twitch_id, dl_dir)
+                    let status = status.clone();
+                    move || {
+                        twitch_dl(&yt_dlp, &options, &info, &twitch_id, dl_dir, &status)
+                    }
                 });
                 t
             }
+            Id::Playlist { .. } => unreachable!(),
             Id::Yt { yt_id } => {
-                yt_dlp.live_from_start(true);
                 let t = std::thread::spawn({
                     let yt_id = yt_id.clone();
+                    let info = info.clone();
                     let dl_dir = dl_dir.clone();
-                    move || yt_dl(&yt_dlp, &yt_id, dl_dir)
+                    let status = status.clone();
+                    move || yt_dl(&yt_dlp, &options, &info, &yt_id, dl_dir, &status)
                 });
                 t
             }
         };
 
+        events::send(&Event::Started { id, info: &info });
+        let timeout_from = (!info.is_live()).then(Instant::now);
         Ok(Self {
             thread,
             info,
             dl_dir,
+            status,
+            progress: (0, Instant::now()),
+            timeout_from,
+            timed_out: None,
         })
     }
+
+    /// Start counting `download_timeout_secs` for a live stream, if it hasn't been
+    /// already.
+    fn stream_ended(&mut self) {
+        self.timeout_from.get_or_insert_with(Instant::now);
+    }
+
+    /// Check how much has been downloaded so far. With a `temp_path`, only the logs are
+    /// in `dl_dir`, so what yt-dlp reports counts too.
+    fn update_progress(&mut self) {
+        let size = fs::read_dir(&self.dl_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok()?.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum::<u64>()
+            .max(self.reported_bytes());
+        // It can't stall before it's started.
+        if size != self.progress.0 || !self.status.started.load(Ordering::Relaxed) {
+            self.progress = (size, Instant::now());
+        }
+    }
+
+    /// How many bytes it has downloaded, as far as we know.
+    fn downloaded_bytes(&self) -> u64 {
+        self.progress.0.max(self.reported_bytes())
+    }
+
+    /// How many bytes yt-dlp last said it had downloaded.
+    fn reported_bytes(&self) -> u64 {
+        let reported = self.status.reported.lock().unwrap();
+        reported
+            .as_ref()
+            .and_then(|x| x.downloaded_bytes)
+            .unwrap_or_default()
+    }
+
+    /// Send `signal` to the yt-dlp process and anything else running for the download.
+    fn signal(&self, signal: libc::c_int) {
+        self.status.signal(signal);
+    }
+}
+
+/// What a download's thread shares with its `Watching`.
+#[derive(Debug, Default)]
+struct DlStatus {
+    /// The PID of the running yt-dlp process, or 0.
+    pid:          AtomicU32,
+    /// The latest progress reported by yt-dlp.
+    reported:     Mutex<Option<DlProgress>>,
+    /// Whether the download itself has started, rather than yt-dlp still getting its
+    /// file name or waiting for a scheduled stream with `wait_for_video`.
+    started:      AtomicBool,
+    /// Set when yt-dlp is stopped for reaching `max_filesize`, so what it downloaded
+    /// is kept.
+    size_limited: AtomicBool,
+    /// The PIDs of other processes running for the download, so they're stopped along
+    /// with yt-dlp instead of being left behind.
+    children:     Mutex<Vec<u32>>,
+}
+
+impl DlStatus {
+    /// Send `signal` to the yt-dlp process and the `children`, if they're running.
+    fn signal(&self, signal: libc::c_int) {
+        let pid = self.pid.load(Ordering::Relaxed);
+        let children = self.children.lock().unwrap();
+        for &pid in std::iter::once(&pid).chain(children.iter()) {
+            if pid != 0 {
+                // SAFETY: kill has no memory safety requirements.
+                unsafe { libc::kill(pid as libc::pid_t, signal) };
+            }
+        }
+    }
+
+    /// Stop `pid` along with the download until it's `remove_child`ed.
+    fn add_child(&self, pid: u32) {
+        self.children.lock().unwrap().push(pid);
+    }
+
+    /// Forget `pid` once it's been waited for, before it can be reused.
+    fn remove_child(&self, pid: u32) {
+        self.children.lock().unwrap().retain(|&x| x != pid);
+    }
+}
+
+/// Download settings that are handled by us rather than yt-dlp.
+#[derive(Debug, Clone, Default)]
+struct DlOptions {
+    /// Where to put finished downloads. Each download keeps the one it started with,
+    /// even if the config is reloaded. This is always absolute, since yt-dlp runs in
+    /// the download's cache directory.
+    out_dir:              PathBuf,
+    /// Write a Kodi-style `.nfo` file next to the download.
+    write_nfo:            bool,
+    /// Keep yt-dlp's info json next to the download.
+    write_info_json:      bool,
+    /// Put the download in a subdirectory named after the uploader.
+    organize_by_uploader: bool,
+    /// Download live streams from the start rather than from when we join. `None` uses
+    /// the platform's default.
+    live_from_start:      Option<bool>,
+    /// yt-dlp's `--wait-for-video` interval for streams that haven't started yet.
+    wait_for_video:       Option<String>,
+    /// How many fragments to download at once for live streams, if not the same as
+    /// for anything else.
+    live_fragments:       Option<u8>,
+    /// Check finished downloads with ffprobe.
+    verify:               bool,
+    /// Download the live chat alongside the video.
+    download_chat:        bool,
+    /// Where to keep yt-dlp's logs, instead of deleting them with the download's cache
+    /// directory.
+    log_dir:              Option<PathBuf>,
+    /// The most bytes per second all downloads together should download at.
+    total_limit_rate:     Option<u64>,
+    /// Move the download's cache directory to `paths::kept_dir()` when it's done,
+    /// with a copy of the download, instead of deleting it.
+    keep_staging:         bool,
+    /// The `cookies_files` to take turns with, shared by every download.
+    cookie_files:         Arc<Mutex<CookieFiles>>,
+    /// Pipe the download to this command's stdin instead of saving it.
+    pipe_to:              Option<Vec<String>>,
+    /// This id's `remux_video`, if it's not the config's.
+    remux_video:          Option<String>,
+}
+
+impl DlOptions {
+    /// Whether to download `id` from the start.
+    fn live_from_start_for(&self, id: &Id) -> bool {
+        // Twitch supports this too, but it's off by default there since it's slower.
+        self.live_from_start.unwrap_or(matches!(id, Id::Yt { .. }))
+    }
+
+    /// Have `yt_dlp` use the next of the `cookies_files`, if there are any.
+    fn next_cookies(&self, yt_dlp: &mut YtDlp) {
+        if let Some(file) = self.cookie_files.lock().unwrap().next() {
+            yt_dlp.cookies(Some(&file));
+        }
+    }
+
+    /// Avoid the cookies file `yt_dlp` used for a while if `error` says it was rate
+    /// limited.
+    fn note_rate_limit(&self, yt_dlp: &YtDlp, error: &str) {
+        if let Some(file) = &yt_dlp.cookies
+            && error.to_lowercase().contains("http error 429")
+        {
+            log!(
+                warn,
+                "{file:?} was rate limited; using other cookies files for a while"
+            );
+            self.cookie_files.lock().unwrap().rate_limited(file);
+        }
+    }
+
+    /// These options with the per-id overrides in `id_options` applied.
+    fn with_overrides(&self, id_options: &IdOptions) -> Self {
+        let mut options = self.clone();
+        if id_options.live_from_start.is_some() {
+            options.live_from_start = id_options.live_from_start;
+        }
+        if let Some(output_dir) = &id_options.output_dir {
+            options.out_dir = absolute(output_dir.clone());
+        }
+        if let Some(keep_staging) = id_options.keep_staging {
+            options.keep_staging = keep_staging;
+        }
+        if id_options.pipe_to.is_some() {
+            options.pipe_to = id_options.pipe_to.clone();
+        }
+        if id_options.remux_video.is_some() {
+            options.remux_video = id_options.remux_video.clone();
+        }
+        options
+    }
+}
+
+/// Settings that can be overridden for a single id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct IdOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    live_from_start:   Option<bool>,
+    /// Put this id's downloads here instead of in `dir`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dir:        Option<PathBuf>,
+    /// Only check for live streams between these local times, e.g. `18:00-23:00`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    active_hours:      Option<String>,
+    /// Only check for live streams on these days, e.g. `["sat", "sun"]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    active_days:       Option<Vec<String>>,
+    /// Keep this id's staging directories instead of deleting them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_staging:      Option<bool>,
+    /// For playlists: keep downloading the other videos when one fails. Otherwise,
+    /// nothing more is downloaded until the failed one is retried. Default: true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continue_on_error: Option<bool>,
+    /// Pipe downloads to this command's stdin instead of saving them, e.g.
+    /// `["ffmpeg", "-i", "-", "-c", "copy", "-f", "flv", "rtmp://..."]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pipe_to:           Option<Vec<String>>,
+    /// Remux this id's downloads into this container instead of `remux_video`, or
+    /// `"none"` to leave them as they are.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remux_video:       Option<String>,
+    /// For YouTube channels: where to look for live streams. Default: live
+    #[serde(skip_serializing_if = "Option::is_none")]
+    live_surface:      Option<LiveSurface>,
+}
+
+/// Where on a YouTube channel to look for live streams.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LiveSurface {
+    /// The channel's `/live` page, which only shows one stream, and not members-only
+    /// ones.
+    #[default]
+    Live,
+    /// The newest few entries on the channel's Streams tab, including members-only
+    /// streams.
+    Streams,
+}
+
+impl IdOptions {
+    /// Whether the id should be checked for live streams at `now`.
+    fn is_active(&self, now: schedule::LocalTime) -> bool {
+        // These are checked when the config is loaded.
+        self.active_hours
+            .as_deref()
+            .is_none_or(|x| schedule::in_hours(x, now).unwrap_or(true))
+            && self
+                .active_days
+                .as_deref()
+                .is_none_or(|x| schedule::on_days(x, now).unwrap_or(true))
+    }
+}
+
+/// An id in the config file, along with its overrides.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Subscription {
+    #[serde(flatten)]
+    id:      Id,
+    #[serde(flatten)]
+    options: IdOptions,
 }
 
 #[derive(Debug, Default)]
 struct InnerSub {
-    pub ids: HashSet<Id>,
+    pub ids:        HashMap<Id, IdOptions>,
+    pub yt_dlp:     YtDlp,
+    pub dl_options: DlOptions,
+    pub notifier:   Notifier,
+
+    pub watching:          HashMap<Id, Watching>,
+    /// Scheduled streams that haven't started yet.
+    pub upcoming:          HashMap<Id, Info>,
+    /// Finished downloads, with their `outcome`s.
+    pub downloaded:        HashMap<Id, Info>,
+    /// Failed downloads to try again.
+    pub retry:             HashSet<Id>,
+    /// Subscriptions that aren't checked until they're resumed.
+    pub paused:            HashSet<Id>,
+    /// The last video started for each YouTube channel id, so a stream that has ended
+    /// isn't picked up again while the channel's live page still points at it.
+    pub last_video:        HashMap<Id, Id>,
+    /// Members-only streams that have been reported, so they're only reported once.
+    pub members_only:      HashSet<String>,
+    /// Cancelled downloads that haven't stopped yet, and whether to keep their
+    /// partially downloaded files.
+    pub cancelled:         HashMap<Id, (Watching, bool)>,
+    /// How long to wait between checking each id.
+    pub poll_delay:        Duration,
+    /// How long a download can go without progress before it's considered stalled.
+    pub stall_timeout:     Duration,
+    /// How long a download can take before it's stopped.
+    pub download_timeout:  Option<Duration>,
+    /// Don't start playlist downloads while this many downloads are running.
+    pub max_concurrent:    Option<usize>,
+    /// How many bytes finished downloads have downloaded since vdl started.
+    pub bytes_downloaded:  u64,
+    /// For updating yt-dlp. This is only `None` until the config is applied.
+    pub update_agent:      Option<ureq::Agent>,
+    /// The yt-dlp release asset to update to.
+    pub yt_dlp_asset:      Option<String>,
+    /// Stop listing playlists at the first video that's already been downloaded.
+    pub break_on_existing: bool,
+}
+
+impl InnerSub {
+    fn apply_config(&mut self, config: &Config) {
+        self.ids = config
+            .ids
+            .iter()
+            .map(|sub| (sub.id.clone(), sub.options.clone()))
+            .collect();
+        self.yt_dlp = config.yt_dlp();
+        let cookie_files = self.dl_options.cookie_files.clone();
+        self.dl_options = config.dl_options();
+        // Downloads in progress share the old ones, so keep them if they're the same.
+        // Either way, files that were rate limited stay avoided.
+        let same = cookie_files.lock().unwrap().files()
+            == self.dl_options.cookie_files.lock().unwrap().files();
+        if same {
+            self.dl_options.cookie_files = cookie_files;
+        } else {
+            self.dl_options
+                .cookie_files
+                .lock()
+                .unwrap()
+                .keep_cooldowns(&cookie_files.lock().unwrap());
+        }
+        self.notifier = config.notifier();
+        self.poll_delay = Duration::from_secs_f32(config.poll_delay_secs.unwrap_or(0.0));
+        self.stall_timeout =
+            Duration::from_secs(config.stall_timeout_secs.unwrap_or(600));
+        self.download_timeout = config.download_timeout_secs.map(Duration::from_secs);
+        self.max_concurrent = config.max_concurrent;
+        self.update_agent = Some(config.update_agent());
+        self.yt_dlp_asset = config.yt_dlp_asset.clone();
+        self.break_on_existing = config.break_on_existing;
+    }
+
+    /// The download options for `id`, with the overrides of the subscription it was
+    /// found through, which is `id` itself if there's no `subscription`.
+    fn dl_options_for(&self, id: &Id, subscription: Option<&Id>) -> DlOptions {
+        match self.ids.get(subscription.unwrap_or(id)) {
+            Some(id_options) => self.dl_options.with_overrides(id_options),
+            None => self.dl_options.clone(),
+        }
+    }
 
-    pub watching:   HashMap<Id, Watching>,
-    pub downloaded: HashMap<Id, Info>,
+    /// Whether to start downloading the stream `video_id` that the channel `id` led to.
+    fn should_start(&self, id: &Id, video_id: &Id, info: &YtLiveInfo) -> bool {
+        // Streams that just ended (`post_live`) can still be downloaded while YouTube
+        // processes the VOD.
+        info.status() != LiveStatus::NotLive
+            && self.last_video.get(id) != Some(video_id)
+            && info
+                .duration
+                .is_none_or(|x| self.yt_dlp.duration_in_bounds(x))
+            && !self.watching.contains_key(video_id)
+            && !self.downloaded.contains_key(video_id)
+    }
+
+    /// Log a snapshot of everything we're keeping track of, for debugging.
+    fn log_state(&self) {
+        log!("State:");
+        log!("  {} ids:", self.ids.len());
+        for (id, options) in &self.ids {
+            log!("    {id} {options:?}");
+        }
+        log!("  {} watching:", self.watching.len());
+        for (id, watching) in &self.watching {
+            log!(
+                "    {id} {:?} (pid {}, {} bytes, last progress {}s ago)",
+                watching.info.title,
+                watching.status.pid.load(Ordering::Relaxed),
+                watching.progress.0,
+                watching.progress.1.elapsed().as_secs()
+            );
+        }
+        log!("  {} paused:", self.paused.len());
+        for id in &self.paused {
+            log!("    {id}");
+        }
+        log!("  {} upcoming:", self.upcoming.len());
+        for (id, info) in &self.upcoming {
+            log!("    {id} {:?}", info.title);
+        }
+        log!("  {} cancelled:", self.cancelled.len());
+        for (id, (watching, keep_partial)) in &self.cancelled {
+            log!(
+                "    {id} {:?} (keep partial: {keep_partial})",
+                watching.info.title
+            );
+        }
+        log!("  {} downloaded:", self.downloaded.len());
+        for (id, info) in &self.downloaded {
+            log!("    {id} {:?} {:?}", info.title, info.outcome);
+        }
+    }
 }
 
 #[derive(Debug, Default)]
 struct Subscriber {
     // YouTube Channel URLs
-    pub inner: Arc<Mutex<InnerSub>>,
+    pub inner:    Arc<Mutex<InnerSub>>,
+    /// Set to skip the rest of the wait and poll for streams now.
+    pub poll_now: Arc<AtomicBool>,
 
     progress_bars:  HashMap<Id, ProgressBar>,
     multi_progress: MultiProgress,
@@ -82,7 +541,7 @@ struct Subscriber {
 
 impl Subscriber {
     pub fn spawn(mut self, silent: bool) -> eyre::Result<()> {
-        let cache_dir = dirs::cache_dir().expect("cache dir").join(NAME);
+        let cache_dir = paths::cache_dir();
         fn pbar() -> ProgressBar {
             let pb = ProgressBar::new_spinner().with_elapsed(Duration::ZERO);
             pb.set_style(
@@ -91,19 +550,26 @@ impl Subscriber {
                 )
                 .unwrap(),
             );
+            // Ticking from its own thread keeps it spinning while we're busy checking
+            // for streams.
+            pb.enable_steady_tick(Duration::from_millis(100));
             pb
         }
 
-        let mut yt_dlp = YtDlp::default();
-        yt_dlp
-            .concurrent_fragments(Some(2))
-            .remux_video(Some("mkv"))
-            .cookies_from_browser(Some("firefox"));
+        let mut yt_dlp = self.inner.lock().unwrap().yt_dlp.clone();
 
         if !cache_dir.exists() {
             fs::create_dir_all(&cache_dir)?;
         }
 
+        let pending = load_pending();
+        let subscription_of = |id: &Id| {
+            pending
+                .iter()
+                .find(|x| x.id == *id)
+                .and_then(|x| x.subscription.as_ref())
+        };
+
         // Handle unfinished downloads
         for entry in fs::read_dir(&cache_dir)? {
             let mut inner = self.inner.lock().unwrap();
@@ -115,7 +581,41 @@ impl Subscriber {
             let id = id.to_string_lossy().to_string();
 
             let Ok(id) = id.parse::<Id>() else { continue };
-            let Ok(watching) = Watching::watch(yt_dlp.clone(), &id) else {
+            let Ok(watching) = Watching::watch(
+                yt_dlp.clone(),
+                inner.dl_options_for(&id, subscription_of(&id)),
+                &id,
+                subscription_of(&id),
+                inner.watching.len(),
+            ) else {
+                continue;
+            };
+            if !silent {
+                let pb = pbar();
+                pb.set_message(id.to_string());
+                let pb = self.multi_progress.add(pb);
+                self.progress_bars.insert(id.clone(), pb);
+            }
+            inner.watching.insert(id.clone(), watching);
+        }
+
+        // Downloads that hadn't started writing to the cache yet. They can't always be
+        // found again by polling, e.g. if the stream has ended since.
+        for PendingDownload {
+            id, subscription, ..
+        } in pending
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.watching.contains_key(&id) {
+                continue;
+            }
+            let Ok(watching) = Watching::watch(
+                yt_dlp.clone(),
+                inner.dl_options_for(&id, subscription.as_ref()),
+                &id,
+                subscription.as_ref(),
+                inner.watching.len(),
+            ) else {
                 continue;
             };
             if !silent {
@@ -126,63 +626,210 @@ impl Subscriber {
             }
             inner.watching.insert(id.clone(), watching);
         }
+        let mut saved_pending = HashSet::new();
 
         loop {
             let mut inner = self.inner.lock().unwrap();
-            let mut remove = vec![];
-            for (id, task) in inner.watching.iter() {
-                if task.thread.is_finished() {
-                    remove.push(id.clone());
+            yt_dlp = inner.yt_dlp.clone();
+            let dl_options = inner.dl_options.clone();
+            inner
+                .watching
+                .values_mut()
+                .for_each(Watching::update_progress);
+            // yt-dlp only stops downloads it knows the size of up front, which live
+            // streams never are.
+            if let Some(limit) = inner
+                .yt_dlp
+                .max_filesize
+                .as_deref()
+                .and_then(|x| parse_size(x).ok())
+            {
+                for (id, watching) in inner.watching.iter() {
+                    if watching.downloaded_bytes() > limit
+                        && !watching.status.size_limited.swap(true, Ordering::Relaxed)
+                    {
+                        log!("{id} reached max_filesize, stopping it");
+                        watching.signal(libc::SIGINT);
+                    }
+                }
+            }
+            if let Some(timeout) = inner.download_timeout {
+                for (id, watching) in inner.watching.iter_mut() {
+                    match watching.timed_out {
+                        // Give yt-dlp a chance to stop ffmpeg before it's killed.
+                        Some(at) if at.elapsed() > TIMEOUT_GRACE => {
+                            watching.signal(libc::SIGKILL);
+                        }
+                        Some(_) => {}
+                        None if watching
+                            .timeout_from
+                            .is_some_and(|x| x.elapsed() > timeout) =>
+                        {
+                            log!(
+                                warn,
+                                "{id} took over {}s, stopping it",
+                                timeout.as_secs()
+                            );
+                            watching.timed_out = Some(Instant::now());
+                            watching.signal(libc::SIGTERM);
+                        }
+                        None => {}
+                    }
                 }
             }
+            finish_downloads(&mut inner, &mut self.progress_bars, silent);
 
-            for r in remove {
-                let watched = inner.watching.remove(&r).unwrap();
-                let ret = watched
-                    .thread
-                    .join()
-                    .expect("Download thread shouldn't panic");
-                let message = match ret {
-                    Ok(_) => {
-                        format!(
-                            "Downloaded {:?} - {}",
-                            watched.info.title, watched.info.uploader
-                        )
+            for id in inner.cancelled.keys().cloned().collect::<Vec<_>>() {
+                let (watched, _) = &inner.cancelled[&id];
+                // yt-dlp might not have been started yet when it was cancelled.
+                watched.signal(libc::SIGTERM);
+                if !watched.thread.is_finished() {
+                    continue;
+                }
+                let (watched, keep_partial) = inner.cancelled.remove(&id).unwrap();
+                inner.bytes_downloaded += watched.downloaded_bytes();
+                let _ = watched.thread.join();
+                let mut message = format!(
+                    "Cancelled {:?} - {}",
+                    watched.info.title, watched.info.uploader
+                );
+                if !keep_partial {
+                    let _ = fs::remove_dir_all(&watched.dl_dir);
+                } else if watched.dl_dir.exists() {
+                    // Out of the cache, or it'd be resumed at the next start.
+                    let kept = kept_staging_dir(&watched.dl_dir, &watched.info);
+                    match remove_staging(&watched.dl_dir, Some(&kept)) {
+                        Ok(()) => {
+                            message =
+                                format!("{message} (partial files kept in {kept:?})")
+                        }
+                        Err(e) => {
+                            log!(error, "Failed to keep the partial files of {id}: {e}")
+                        }
                     }
+                }
+                log::record(log::Level::Info, message.clone());
+                if let Some(pb) = self.progress_bars.remove(&id) {
+                    pb.finish_with_message(message);
+                }
+                let mut info = watched.info;
+                info.finished_at = Some(unix_now());
+                info.outcome = Some(Outcome::Cancelled);
+                events::send(&Event::Finished {
+                    id:   &id,
+                    info: &info,
+                });
+                inner.downloaded.insert(id, info);
+            }
+
+            for id in std::mem::take(&mut inner.retry) {
+                let subscription = inner
+                    .downloaded
+                    .get(&id)
+                    .and_then(|x| x.subscription.clone());
+                let watching = match Watching::watch(
+                    yt_dlp.clone(),
+                    inner.dl_options_for(&id, subscription.as_ref()),
+                    &id,
+                    subscription.as_ref(),
+                    inner.watching.len(),
+                ) {
+                    Ok(x) => x,
                     Err(e) => {
-                        format!(
-                            "Failed to download {:?} - {}: {e}",
-                            watched.info.title, watched.info.uploader
-                        )
+                        if let Some(info) = inner.downloaded.get_mut(&id) {
+                            info.outcome = Some(Outcome::Failed {
+                                error: e.to_string(),
+                            });
+                        }
+                        continue;
                     }
                 };
+                inner.downloaded.remove(&id);
+                inner.watching.insert(id.clone(), watching);
                 if !silent {
-                    let pb = self.progress_bars.remove(&r).unwrap();
-                    pb.finish_with_message(message);
+                    let pb = pbar();
+                    pb.set_message(id.to_string());
+                    let pb = self.multi_progress.add(pb);
+                    self.progress_bars.insert(id.clone(), pb);
                 }
-                inner.downloaded.insert(r, watched.info);
             }
 
-            for id in inner.ids.clone() {
+            let now = schedule::LocalTime::now();
+            for (i, (id, id_options)) in inner.ids.clone().into_iter().enumerate() {
+                if !id_options.is_active(now) || inner.paused.contains(&id) {
+                    continue;
+                }
+                let dl_options = dl_options.with_overrides(&id_options);
+                // Checking makes most of the requests, so it takes turns too.
+                let mut yt_dlp = yt_dlp.clone();
+                dl_options.next_cookies(&mut yt_dlp);
+                if i > 0 && !inner.poll_delay.is_zero() {
+                    // Spread the checks out to avoid being rate limited, without blocking
+                    // IPC in the meantime.
+                    let poll_delay = inner.poll_delay;
+                    std::mem::drop(inner);
+                    std::thread::sleep(poll_delay);
+                    inner = self.inner.lock().unwrap();
+                }
                 match &id {
                     Id::Yt { yt_id } => {
-                        let Ok(Some(info)) = live_info(&yt_dlp, &yt_id) else {
-                            continue;
+                        let surface = id_options.live_surface.unwrap_or_default();
+                        // Don't block IPC while it's retrying.
+                        std::mem::drop(inner);
+                        let info = live_info(&yt_dlp, &yt_id, surface);
+                        inner = self.inner.lock().unwrap();
+                        let info = match info {
+                            Ok(Some(info)) => info,
+                            Ok(None) => {
+                                if let Some(last) = inner.last_video.get(&id).cloned()
+                                    && let Some(watching) = inner.watching.get_mut(&last)
+                                {
+                                    watching.stream_ended();
+                                }
+                                continue;
+                            }
+                            Err(e) => {
+                                dl_options.note_rate_limit(&yt_dlp, &e.to_string());
+                                if let error::Error::MembersOnly { url } = &e
+                                    && inner.members_only.insert(url.clone())
+                                {
+                                    log!(error, "{id}: {e}");
+                                }
+                                continue;
+                            }
                         };
                         let video_id = Id::Yt {
                             yt_id: info.id.clone(),
                         };
-                        if !(info.is_live || info.was_live)
-                            || inner.watching.contains_key(&video_id)
-                            || inner.downloaded.contains_key(&video_id)
+                        if let Some(last) = inner.last_video.get(&id).cloned()
+                            && (last != video_id || info.status() != LiveStatus::IsLive)
+                            && let Some(watching) = inner.watching.get_mut(&last)
                         {
+                            watching.stream_ended();
+                        }
+                        // With `wait_for_video`, yt-dlp can wait for scheduled streams
+                        // itself and start the moment they do.
+                        if info.is_upcoming() && dl_options.wait_for_video.is_none() {
+                            let mut info = Info::from(info);
+                            info.subscription = Some(id.clone());
+                            inner.upcoming.insert(video_id, info);
                             continue;
                         }
-                        let Ok(watching) = Watching::watch(yt_dlp.clone(), &video_id)
-                        else {
+                        inner.upcoming.remove(&video_id);
+                        if !inner.should_start(&id, &video_id, &info) {
+                            continue;
+                        }
+                        let Ok(watching) = Watching::watch(
+                            yt_dlp.clone(),
+                            dl_options.clone(),
+                            &video_id,
+                            Some(&id),
+                            inner.watching.len(),
+                        ) else {
                             continue;
                         };
                         inner.watching.insert(video_id.clone(), watching);
+                        inner.last_video.insert(id.clone(), video_id.clone());
                         if !silent {
                             let pb = pbar();
                             pb.set_message(video_id.to_string());
@@ -190,12 +837,31 @@ impl Subscriber {
                             self.progress_bars.insert(video_id.clone(), pb);
                         }
                     }
+                    // Only checked with a timeout, since it's another request per poll.
+                    Id::Twitch { twitch_id }
+                        if inner.download_timeout.is_some()
+                            && inner.watching.contains_key(&id)
+                            && !twitch_is_live(&yt_dlp, twitch_id) =>
+                    {
+                        inner.watching.get_mut(&id).unwrap().stream_ended();
+                    }
                     Id::Twitch { twitch_id }
                         if !inner.watching.contains_key(&id)
-                            && !inner.downloaded.contains_key(&id)
-                            && twitch_is_live(&yt_dlp, &twitch_id) =>
+                            && !inner.downloaded.contains_key(&id) =>
                     {
-                        let Ok(watching) = Watching::watch(yt_dlp.clone(), &id) else {
+                        std::mem::drop(inner);
+                        let is_live = twitch_is_live(&yt_dlp, &twitch_id);
+                        inner = self.inner.lock().unwrap();
+                        if !is_live || inner.watching.contains_key(&id) {
+                            continue;
+                        }
+                        let Ok(watching) = Watching::watch(
+                            yt_dlp.clone(),
+                            dl_options.clone(),
+                            &id,
+                            None,
+                            inner.watching.len(),
+                        ) else {
                             continue;
                         };
                         inner.watching.insert(id.clone(), watching);
@@ -206,28 +872,524 @@ impl Subscriber {
                             self.progress_bars.insert(id.clone(), pb);
                         }
                     }
+                    Id::Playlist { url } => {
+                        let continue_on_error =
+                            id_options.continue_on_error.unwrap_or(true);
+                        let break_on_existing = inner.break_on_existing;
+                        std::mem::drop(inner);
+                        let entries = playlist_entries(
+                            &yt_dlp,
+                            url,
+                            continue_on_error,
+                            break_on_existing,
+                        );
+                        inner = self.inner.lock().unwrap();
+                        let Ok(entries) = entries else {
+                            continue;
+                        };
+                        let failed = entries.iter().any(|yt_id| {
+                            let id = Id::Yt {
+                                yt_id: yt_id.clone(),
+                            };
+                            inner.downloaded.get(&id).is_some_and(|x| {
+                                matches!(x.outcome, Some(Outcome::Failed { .. }))
+                            })
+                        });
+                        if failed && !continue_on_error {
+                            continue;
+                        }
+                        let archived = read_archive(yt_dlp.download_archive.as_deref());
+                        for yt_id in entries {
+                            // Live streams are always started since they can't wait, but
+                            // these can.
+                            if inner
+                                .max_concurrent
+                                .is_some_and(|max| inner.watching.len() >= max)
+                            {
+                                break;
+                            }
+                            let video_id = Id::Yt { yt_id };
+                            if archived.contains(&video_id)
+                                || inner.watching.contains_key(&video_id)
+                                || inner.downloaded.contains_key(&video_id)
+                            {
+                                continue;
+                            }
+                            let Ok(watching) = Watching::watch(
+                                yt_dlp.clone(),
+                                dl_options.clone(),
+                                &video_id,
+                                Some(&id),
+                                inner.watching.len(),
+                            ) else {
+                                continue;
+                            };
+                            inner.watching.insert(video_id.clone(), watching);
+                            if !silent {
+                                let pb = pbar();
+                                pb.set_message(video_id.to_string());
+                                let pb = self.multi_progress.add(pb);
+                                self.progress_bars.insert(video_id.clone(), pb);
+                            }
+                        }
+                    }
                     _ => {}
                 }
             }
 
+            // Finished downloads were removed above, so they won't be restored.
+            let pending = inner.watching.keys().cloned().collect::<HashSet<_>>();
+            if pending != saved_pending {
+                match save_pending(&inner.watching) {
+                    Ok(()) => saved_pending = pending,
+                    Err(e) => log!(error, "Failed to save pending downloads: {e}"),
+                }
+            }
+
             std::mem::drop(inner);
 
-            for _ in 0..(45 * 1000 / 100) {
+            for tick in 0..(45 * 1000 / 100) {
+                if self.poll_now.swap(false, Ordering::Relaxed) {
+                    break;
+                }
                 std::thread::sleep(Duration::from_millis(100));
-                self.progress_bars.values().for_each(|pb| pb.tick());
+                if tick % 10 == 0 {
+                    self.show_progress();
+                    self.send_progress();
+                }
+            }
+        }
+    }
+
+    /// Send the latest progress of each download to anyone subscribed to events.
+    fn send_progress(&self) {
+        let inner = self.inner.lock().unwrap();
+        for (id, watching) in &inner.watching {
+            if let Some(progress) = &*watching.status.reported.lock().unwrap() {
+                events::send(&Event::Progress { id, progress });
             }
         }
     }
+
+    /// Show the latest progress of each download in its progress bar.
+    fn show_progress(&self) {
+        let inner = self.inner.lock().unwrap();
+        for (id, pb) in &self.progress_bars {
+            let Some(progress) = inner
+                .watching
+                .get(id)
+                .and_then(|x| x.status.reported.lock().unwrap().clone())
+            else {
+                continue;
+            };
+            pb.set_message(format!("{id} — {progress}"));
+        }
+    }
+}
+
+/// Read the subscriptions saved by `save_paused`.
+fn load_paused() -> HashSet<Id> {
+    let path = paths::paused_file();
+    let Ok(json) = fs::read_to_string(&path) else {
+        return HashSet::new();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        log!(warn, "Ignoring {path:?}: {e}");
+        HashSet::new()
+    })
+}
+
+/// Save the paused subscriptions so they stay paused after a restart.
+fn save_paused(paused: &HashSet<Id>) -> eyre::Result<()> {
+    let path = paths::paused_file();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string(paused)?)?;
+    Ok(())
+}
+
+/// A download that was in progress when vdl stopped.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingDownload {
+    id:           Id,
+    /// Only for people reading the file; downloads are resumed by id.
+    url:          String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subscription: Option<Id>,
+}
+
+/// Read the downloads saved by `save_pending`.
+fn load_pending() -> Vec<PendingDownload> {
+    let path = paths::pending_file();
+    let Ok(json) = fs::read_to_string(&path) else {
+        return vec![];
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        log!(warn, "Ignoring {path:?}: {e}");
+        vec![]
+    })
+}
+
+/// Save the downloads in progress so they can be resumed after a restart.
+fn save_pending(watching: &HashMap<Id, Watching>) -> eyre::Result<()> {
+    let path = paths::pending_file();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let pending = watching
+        .iter()
+        .map(|(id, x)| PendingDownload {
+            id:           id.clone(),
+            url:          x.info.webpage_url.clone(),
+            subscription: x.info.subscription.clone(),
+        })
+        .collect::<Vec<_>>();
+    fs::write(&path, serde_json::to_string(&pending)?)?;
+    Ok(())
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct Config {
-    dir: Option<PathBuf>,
+    dir:                   Option<PathBuf>,
+    /// Keep the original file after remuxing, next to the remuxed one.
+    #[serde(default)]
+    keep_video:            bool,
+    /// Send download notifications to this ntfy topic.
+    ntfy_topic:            Option<String>,
+    /// The ntfy server to use, default: https://ntfy.sh
+    ntfy_server:           Option<String>,
+    /// Send download notifications to this Discord webhook URL.
+    discord_webhook:       Option<String>,
+    /// Embed the stream's chapters into the video.
+    #[serde(default)]
+    embed_chapters:        bool,
+    /// Attach the full info JSON to the video. Only works with mkv.
+    #[serde(default)]
+    embed_info_json:       bool,
+    /// Bypass geographic restrictions by faking the X-Forwarded-For header.
+    #[serde(default)]
+    geo_bypass:            bool,
+    /// Two-letter ISO 3166-2 country code to use for the geo bypass.
+    geo_bypass_country:    Option<String>,
+    /// Bind yt-dlp's connections to this local IP address.
+    source_address:        Option<String>,
+    /// Make all of yt-dlp's connections over IPv4.
+    #[serde(default)]
+    force_ipv4:            bool,
+    /// Make all of yt-dlp's connections over IPv6.
+    #[serde(default)]
+    force_ipv6:            bool,
+    /// Skip streams shorter than this many seconds.
+    min_duration_secs:     Option<u64>,
+    /// Skip streams longer than this many seconds.
+    max_duration_secs:     Option<u64>,
+    /// Skip streams less than this many pixels tall, and audio-only ones.
+    min_height:            Option<u32>,
+    /// Seconds yt-dlp should sleep between requests during data extraction.
+    sleep_requests:        Option<f32>,
+    /// Seconds yt-dlp should sleep before each download, or the least if
+    /// `max_sleep_interval` is set.
+    sleep_interval:        Option<f32>,
+    /// Sleep a random amount between `sleep_interval` and this many seconds instead.
+    max_sleep_interval:    Option<f32>,
+    /// Restart the download when its rate drops below this, e.g. `100K`.
+    throttled_rate:        Option<String>,
+    /// Limit the download rate of all downloads together to this, e.g. `10M`. It's
+    /// split evenly between the downloads running when each one starts.
+    total_limit_rate:      Option<String>,
+    /// yt-dlp's download buffer size, e.g. `16K`.
+    buffer_size:           Option<String>,
+    /// Download HTTP formats in chunks of this size, e.g. `10M`.
+    http_chunk_size:       Option<String>,
+    /// How many fragments of HLS and DASH formats to download at once. Default: 2
+    concurrent_fragments:  Option<u8>,
+    /// `concurrent_fragments` for live streams, which are all fragments. Default:
+    /// `concurrent_fragments`
+    live_fragments:        Option<u8>,
+    /// Stop downloads that get larger than this, e.g. `50G`.
+    max_filesize:          Option<String>,
+    /// yt-dlp's `-S` format sort, e.g. `res:1080,fps,vcodec:vp9`.
+    format_sort:           Option<String>,
+    /// The container for merged video and audio formats, e.g. `mp4`. `remux_video`
+    /// still applies afterwards.
+    merge_output_format:   Option<String>,
+    /// Skip streams that aren't suitable for someone this old.
+    age_limit:             Option<u32>,
+    /// Where to load cookies from, as `BROWSER[+KEYRING][:PROFILE][::CONTAINER]`.
+    /// Default: firefox
+    cookies_from_browser:  Option<String>,
+    /// Netscape cookies files to use instead of `cookies_from_browser`, e.g. one per
+    /// account. Downloads take turns with them, and a file that gets rate limited is
+    /// avoided for a while.
+    #[serde(default)]
+    cookies_files:         Vec<PathBuf>,
+    /// Seconds to wait between checking each id for live streams.
+    poll_delay_secs:       Option<f32>,
+    /// The yt-dlp `--download-archive` file, so finished downloads are never fetched
+    /// again. Default: `$XDG_STATE_HOME/vdl/archive.txt`
+    download_archive:      Option<PathBuf>,
+    /// Stop listing a playlist at the first video in `download_archive`. This assumes
+    /// the newest videos come first, like a channel's uploads.
+    #[serde(default)]
+    break_on_existing:     bool,
+    /// A yt-dlp config file to load options from.
+    config_location:       Option<PathBuf>,
+    /// Arguments passed to yt-dlp verbatim, for options vdl doesn't have. These come
+    /// after vdl's own options, so they can override them, but before the per-download
+    /// ones like `--output`.
+    #[serde(default)]
+    extra_args:            Vec<String>,
+    /// Have yt-dlp download into this directory and move finished downloads into place
+    /// itself, instead of staging them in vdl's cache directory.
+    temp_path:             Option<PathBuf>,
+    /// The User-Agent to use when updating yt-dlp from GitHub. Default: "VDL via ureq"
+    update_user_agent:     Option<String>,
+    /// The yt-dlp release asset to download, e.g. `yt-dlp_musllinux` where the
+    /// default doesn't run. Default: "yt-dlp_linux"
+    yt_dlp_asset:          Option<String>,
+    /// Give up on updating yt-dlp after this many seconds. Default: 300
+    update_timeout_secs:   Option<u64>,
+    /// Seconds to wait for HTTP connections (updates and notifications). Default: 10
+    connect_timeout_secs:  Option<u64>,
+    /// Seconds to wait for an HTTP response once connected. Default: 30
+    read_timeout_secs:     Option<u64>,
+    /// Don't start downloading playlist entries while this many downloads are running.
+    /// Live streams are always started, since they can't wait.
+    max_concurrent:        Option<usize>,
+    /// Seconds a download can go without progress before `vdl ipc health` fails.
+    /// Default: 600
+    stall_timeout_secs:    Option<u64>,
+    /// Seconds a download can take before it's stopped and marked failed, so it can be
+    /// retried. Live streams get this long after they end.
+    download_timeout_secs: Option<u64>,
+    /// Only download streams matching this yt-dlp `--match-filter` expression.
+    match_filter:          Option<String>,
+    /// Also listen for IPC on this TCP address, e.g. `0.0.0.0:7878`. Requires `ipc_token`.
+    /// Changing this requires a restart.
+    ipc_tcp_addr:          Option<String>,
+    /// A shared secret IPC clients must send when connecting over TCP. Changing this
+    /// requires a restart.
+    ipc_token:             Option<String>,
+    /// Write a Kodi-style `.nfo` file next to each download for media servers.
+    #[serde(default)]
+    write_nfo:             bool,
+    /// Keep yt-dlp's info json next to each download, for the formats, file size, and
+    /// everything else yt-dlp knew about it. Otherwise it's deleted with the cache.
+    #[serde(default)]
+    write_info_json:       bool,
+    /// Put downloads in subdirectories named after their uploader.
+    #[serde(default)]
+    organize_by_uploader:  bool,
+    /// Restrict file names to ASCII characters and avoid "&" and spaces.
     #[serde(default)]
-    ids: HashSet<Id>,
+    restrict_filenames:    bool,
+    /// Force file names to be Windows-compatible, e.g. for SMB shares.
+    #[serde(default)]
+    windows_filenames:     bool,
+    /// Save the stream's description to a `.description` file next to it.
+    #[serde(default)]
+    write_description:     bool,
+    /// Save the thumbnail to a `.jpg` file next to the video.
+    #[serde(default)]
+    write_thumbnail:       bool,
+    /// Embed subtitles in these languages, as a yt-dlp `--sub-langs` value like
+    /// `en.*,ja`. Missing languages are skipped.
+    sub_langs:             Option<String>,
+    /// Give downloads the current time as their modification time, instead of the
+    /// upload date.
+    #[serde(default)]
+    no_mtime:              bool,
+    /// The container to remux downloads into, or `"none"` to leave them as they are.
+    /// Like yt-dlp's `--remux-video`, this can also depend on what they're in to begin
+    /// with, e.g. `webm>mkv/mp4`. Can be overridden per id. Default: mkv
+    remux_video:           Option<String>,
+    /// Download live streams from the start. Defaults to on for YouTube and off for
+    /// Twitch, and can be overridden per id.
+    live_from_start:       Option<bool>,
+    /// How often yt-dlp should check whether a scheduled stream has started, in seconds
+    /// as `MIN[-MAX]`.
+    wait_for_video:        Option<String>,
+    /// Read finished downloads through with ffprobe to find corrupt segments. This
+    /// needs ffprobe on the `PATH`, and takes a while for long streams.
+    #[serde(default)]
+    verify:                bool,
+    /// Save the live chat next to downloads, where there is one.
+    #[serde(default)]
+    download_chat:         bool,
+    /// Keep yt-dlp's output for each download here, e.g. `yt-<id>.stderr.log`.
+    /// Otherwise it's deleted once the download succeeds.
+    log_dir:               Option<PathBuf>,
+    /// Keep a finished download's staging directory, with a copy of the download and
+    /// yt-dlp's logs, for looking into suspect downloads. Can be overridden per id.
+    #[serde(default)]
+    keep_staging:          bool,
+    // Tables have to come after plain values when serializing to TOML, so this must
+    // stay last.
+    #[serde(default)]
+    ids:                   Vec<Subscription>,
 }
 
 impl Config {
+    fn yt_dlp(&self) -> YtDlp {
+        let mut yt_dlp = YtDlp::default();
+        yt_dlp
+            .concurrent_fragments(Some(self.concurrent_fragments.unwrap_or(2)))
+            .remux_video(match self.remux_video.as_deref() {
+                None => Some("mkv"),
+                Some("none") => None,
+                Some(format) => Some(format),
+            })
+            .cookies_from_browser(match self.cookies_files.is_empty() {
+                true => Some(self.cookies_from_browser.as_deref().unwrap_or("firefox")),
+                false => None,
+            })
+            .cookies(self.cookies_files.first().map(PathBuf::as_path))
+            .keep_video(self.keep_video)
+            .embed_chapters(self.embed_chapters)
+            .embed_info_json(self.embed_info_json)
+            .geo_bypass(self.geo_bypass)
+            .geo_bypass_country(self.geo_bypass_country.as_deref())
+            .source_address(self.source_address.as_deref())
+            .force_ipv4(self.force_ipv4)
+            .force_ipv6(self.force_ipv6)
+            .restrict_filenames(self.restrict_filenames)
+            .windows_filenames(self.windows_filenames)
+            .write_description(self.write_description)
+            .write_thumbnail(self.write_thumbnail)
+            .sub_langs(self.sub_langs.as_deref())
+            .no_mtime(self.no_mtime)
+            .min_duration(self.min_duration_secs)
+            .min_height(self.min_height)
+            .max_duration(self.max_duration_secs)
+            .match_filter(self.match_filter.as_deref())
+            .sleep_requests(self.sleep_requests)
+            .sleep_interval(self.sleep_interval)
+            .max_sleep_interval(self.max_sleep_interval)
+            .throttled_rate(self.throttled_rate.as_deref())
+            .download_archive(Some(
+                &self
+                    .download_archive
+                    .clone()
+                    .unwrap_or_else(paths::download_archive),
+            ))
+            .temp_path(self.temp_path.as_deref())
+            .config_location(self.config_location.as_deref())
+            .extra_args(&self.extra_args)
+            .buffer_size(self.buffer_size.as_deref())
+            .http_chunk_size(self.http_chunk_size.as_deref())
+            .max_filesize(self.max_filesize.as_deref())
+            .format_sort(self.format_sort.as_deref())
+            .merge_output_format(self.merge_output_format.as_deref())
+            .age_limit(self.age_limit);
+        yt_dlp
+    }
+
+    fn dl_options(&self) -> DlOptions {
+        let out_dir = self
+            .dir
+            .clone()
+            .or_else(dirs::video_dir)
+            .unwrap_or_else(|| PathBuf::from("."));
+        DlOptions {
+            out_dir:              absolute(out_dir),
+            write_nfo:            self.write_nfo,
+            write_info_json:      self.write_info_json,
+            organize_by_uploader: self.organize_by_uploader,
+            live_from_start:      self.live_from_start,
+            wait_for_video:       self.wait_for_video.clone(),
+            live_fragments:       self.live_fragments,
+            verify:               self.verify,
+            download_chat:        self.download_chat,
+            log_dir:              self.log_dir.clone().map(absolute),
+            keep_staging:         self.keep_staging,
+            cookie_files:         Arc::new(Mutex::new(CookieFiles::new(
+                self.cookies_files.iter().cloned().map(absolute).collect(),
+            ))),
+            // Only set per id.
+            pipe_to:              None,
+            remux_video:          None,
+            total_limit_rate:     self
+                .total_limit_rate
+                .as_deref()
+                .and_then(|x| parse_rate(x).ok()),
+        }
+    }
+
+    /// How long to wait for HTTP connections and responses.
+    fn http_timeouts(&self) -> (Duration, Duration) {
+        (
+            Duration::from_secs(self.connect_timeout_secs.unwrap_or(10)),
+            Duration::from_secs(self.read_timeout_secs.unwrap_or(30)),
+        )
+    }
+
+    /// The HTTP agent for updating yt-dlp.
+    fn update_agent(&self) -> ureq::Agent {
+        let timeout = Duration::from_secs(self.update_timeout_secs.unwrap_or(300));
+        let (connect_timeout, read_timeout) = self.http_timeouts();
+        ureq::Agent::config_builder()
+            .user_agent(
+                self.update_user_agent
+                    .as_deref()
+                    .unwrap_or(yt_dlp::USER_AGENT),
+            )
+            .timeout_global(Some(timeout))
+            .timeout_connect(Some(connect_timeout))
+            .timeout_recv_response(Some(read_timeout))
+            .build()
+            .new_agent()
+    }
+
+    fn notifier(&self) -> Notifier {
+        let (connect_timeout, read_timeout) = self.http_timeouts();
+        Notifier {
+            ntfy_topic:      self.ntfy_topic.clone(),
+            ntfy_server:     self.ntfy_server.clone(),
+            discord_webhook: self.discord_webhook.clone(),
+            connect_timeout: Some(connect_timeout),
+            read_timeout:    Some(read_timeout),
+        }
+    }
+
+    /// This config with the defaults and per-id overrides filled in, as it will
+    /// actually be used. Secrets are redacted.
+    fn resolved(&self) -> Self {
+        const REDACTED: &str = "<redacted>";
+
+        let mut config = self.clone();
+        config.dir = config.dir.or_else(dirs::video_dir);
+        if config.cookies_files.is_empty() {
+            config
+                .cookies_from_browser
+                .get_or_insert_with(|| "firefox".to_string());
+        }
+        config.remux_video.get_or_insert_with(|| "mkv".to_string());
+        let fragments = *config.concurrent_fragments.get_or_insert(2);
+        config.live_fragments.get_or_insert(fragments);
+        config
+            .download_archive
+            .get_or_insert_with(paths::download_archive);
+        if config.ntfy_topic.is_some() {
+            config
+                .ntfy_server
+                .get_or_insert_with(|| notify::NTFY_SERVER.to_string());
+        }
+        if config.ipc_token.is_some() {
+            config.ipc_token = Some(REDACTED.to_string());
+        }
+        if config.discord_webhook.is_some() {
+            config.discord_webhook = Some(REDACTED.to_string());
+        }
+
+        let dl_options = self.dl_options();
+        for sub in &mut config.ids {
+            let dl_options = dl_options.with_overrides(&sub.options);
+            sub.options.live_from_start = Some(dl_options.live_from_start_for(&sub.id));
+        }
+        config
+    }
+
     fn load(path: &Path) -> eyre::Result<Self> {
         let config = if path.exists() {
             let toml = fs::read_to_string(path)?;
@@ -245,368 +1407,2748 @@ impl Config {
             f.write_all(toml.as_bytes())?;
             config
         };
+        config.validate()?;
         Ok(config)
     }
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-enum IpcRequest {
-    GetWatching,
-    GetDownloaded,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-enum IpcResponse {
-    Watching(Vec<Info>),
-    Downloaded(Vec<Info>),
-    Error(String),
-}
-
-struct Ipc {
-    inner_sub: Arc<Mutex<InnerSub>>,
-    listener:  UnixListener,
-}
-
-impl Ipc {
-    fn new(inner_sub: Arc<Mutex<InnerSub>>) -> eyre::Result<Self> {
-        let runtime_dir = dirs::runtime_dir().expect("User runtime dir").join(NAME);
-        let socket = runtime_dir.join("ipc.sock");
 
-        if !runtime_dir.exists() {
-            fs::create_dir_all(&runtime_dir)?;
+    fn validate(&self) -> eyre::Result<()> {
+        if let Some(dir) = &self.dir {
+            check_output_dir(dir).map_err(|e| eyre!("dir: {e}"))?;
         }
-        if socket.exists() {
-            fs::remove_file(&socket).map_err(|e| {
-                eyre!("Failed to remove old socket, maybe a server is running?\n{e}")
-            })?;
+        for sub in &self.ids {
+            sub.id.validate()?;
+            if let Some(dir) = &sub.options.output_dir {
+                check_output_dir(dir)
+                    .map_err(|e| eyre!("{}: output_dir: {e}", sub.id))?;
+            }
+            let now = schedule::LocalTime::now();
+            if let Some(hours) = &sub.options.active_hours {
+                schedule::in_hours(hours, now)
+                    .map_err(|e| eyre!("{}: active_hours: {e}", sub.id))?;
+            }
+            if let Some(days) = &sub.options.active_days {
+                schedule::on_days(days, now)
+                    .map_err(|e| eyre!("{}: active_days: {e}", sub.id))?;
+            }
+            if sub.options.continue_on_error.is_some()
+                && !matches!(sub.id, Id::Playlist { .. })
+            {
+                return Err(eyre!(
+                    "{}: continue_on_error only applies to playlists",
+                    sub.id
+                ));
+            }
+            if sub.options.pipe_to.as_ref().is_some_and(Vec::is_empty) {
+                return Err(eyre!("{}: pipe_to needs a command", sub.id));
+            }
+            if sub.options.live_surface.is_some() && !matches!(sub.id, Id::Yt { .. }) {
+                return Err(eyre!(
+                    "{}: live_surface only applies to YouTube channels",
+                    sub.id
+                ));
+            }
+            if let Some(remux_video) = &sub.options.remux_video {
+                yt_dlp::check_remux_video(remux_video)
+                    .map_err(|e| eyre!("{}: remux_video: {e}", sub.id))?;
+            }
         }
-        Ok(Self {
-            inner_sub,
-            listener: UnixListener::bind(&socket)?,
-        })
-    }
-
-    fn spawn(self) -> eyre::Result<()> {
-        let mut message_body = Vec::new();
-        loop {
-            let (mut stream, _sock_addr) = self.listener.accept()?;
-
-            message_body.clear();
-            stream.read_to_end(&mut message_body)?;
-
-            let response = match serde_json::de::from_slice(&message_body) {
-                Ok(request) => self.handle_request(request),
-                Err(e) => IpcResponse::Error(format!(
-                    "Error: failed to parse JSON request: {e}"
-                )),
+        if self.ipc_tcp_addr.is_some()
+            && self.ipc_token.as_deref().is_none_or(str::is_empty)
+        {
+            return Err(eyre!(
+                "ipc_tcp_addr: an ipc_token is required to use IPC over TCP"
+            ));
+        }
+        if self
+            .poll_delay_secs
+            .is_some_and(|x| !x.is_finite() || x < 0.0)
+        {
+            return Err(eyre!(
+                "poll_delay_secs: must be a positive number of seconds"
+            ));
+        }
+        for (name, secs) in [
+            ("sleep_interval", self.sleep_interval),
+            ("max_sleep_interval", self.max_sleep_interval),
+        ] {
+            if secs.is_some_and(|x| !x.is_finite() || x < 0.0) {
+                return Err(eyre!("{name}: must be a positive number of seconds"));
+            }
+        }
+        match (self.sleep_interval, self.max_sleep_interval) {
+            (None, Some(_)) => {
+                return Err(eyre!("max_sleep_interval: sleep_interval must be set too"));
+            }
+            (Some(min), Some(max)) if max < min => {
+                return Err(eyre!(
+                    "max_sleep_interval: must be at least sleep_interval ({min})"
+                ));
+            }
+            _ => {}
+        }
+        if let Some(country) = &self.geo_bypass_country
+            && (country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            return Err(eyre!(
+                "geo_bypass_country: {country:?} is not a two-letter country code"
+            ));
+        }
+        if let Some(address) = &self.source_address
+            && address.parse::<std::net::IpAddr>().is_err()
+        {
+            return Err(eyre!("source_address: {address:?} is not an IP address"));
+        }
+        if self.concurrent_fragments == Some(0) || self.live_fragments == Some(0) {
+            return Err(eyre!(
+                "concurrent_fragments and live_fragments must be at least 1"
+            ));
+        }
+        if self.download_timeout_secs == Some(0) {
+            return Err(eyre!("download_timeout_secs must be at least 1"));
+        }
+        if let Some(rate) = &self.total_limit_rate {
+            parse_rate(rate).map_err(|e| eyre!("total_limit_rate: {e}"))?;
+        }
+        if let Some(size) = &self.max_filesize {
+            parse_size(size).map_err(|e| eyre!("max_filesize: {e}"))?;
+        }
+        if let Some(asset) = &self.yt_dlp_asset
+            && (asset.is_empty() || asset.contains('/'))
+        {
+            return Err(eyre!("yt_dlp_asset: {asset:?} isn't a file name"));
+        }
+        if let Some(remux_video) = &self.remux_video {
+            yt_dlp::check_remux_video(remux_video)
+                .map_err(|e| eyre!("remux_video: {e}"))?;
+        }
+        if self.force_ipv4 && self.force_ipv6 {
+            return Err(eyre!("force_ipv4 and force_ipv6 can't both be set"));
+        }
+        if let Some(spec) = &self.cookies_from_browser {
+            yt_dlp::check_cookies_from_browser(spec)
+                .map_err(|e| eyre!("cookies_from_browser: {e}"))?;
+            if !self.cookies_files.is_empty() {
+                return Err(eyre!(
+                    "cookies_from_browser and cookies_files can't both be set"
+                ));
+            }
+        }
+        if let Some(file) = self.cookies_files.iter().find(|x| !x.is_file()) {
+            return Err(eyre!("cookies_files: {file:?} doesn't exist"));
+        }
+        if self.verify
+            && std::process::Command::new("ffprobe")
+                .arg("-version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_err()
+        {
+            log!(warn, "verify is on, but ffprobe couldn't be run");
+        }
+        if self
+            .sub_langs
+            .as_deref()
+            .is_some_and(|x| x.split(',').any(|lang| lang.trim() == "live_chat"))
+        {
+            log!(
+                warn,
+                "sub_langs includes live_chat, which holds up live downloads until \
+                 the stream ends; download_chat doesn't"
+            );
+        }
+        if self.embed_info_json {
+            let container = match self.remux_video.as_deref() {
+                None => Some("mkv"),
+                Some("none") => self.merge_output_format.as_deref(),
+                Some(format) => Some(format),
             };
-            let response_json = match serde_json::ser::to_vec(&response) {
-                Ok(x) => x,
-                Err(e) => serde_json::ser::to_vec(&IpcResponse::Error(format!(
-                    "Error: failed to serialize response: {e}"
-                )))
-                .unwrap(),
+            let all_mkv = container.is_some_and(|x| {
+                x.split('/').all(|rule| {
+                    matches!(rule.rsplit('>').next().map(str::trim), Some("mkv" | "mka"))
+                })
+            });
+            if !all_mkv {
+                log!(
+                    warn,
+                    "embed_info_json only works with mkv, but downloads will be {}",
+                    container.unwrap_or("in whatever container yt-dlp picks")
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that downloads can be put in `dir`.
+fn check_output_dir(dir: &Path) -> eyre::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    if !dir.is_dir() {
+        return Err(eyre!("{dir:?} is not a directory"));
+    }
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes())?;
+    // SAFETY: `path` is a valid C string.
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK) } != 0 {
+        return Err(eyre!("{dir:?} is not writable"));
+    }
+    Ok(())
+}
+
+/// A request as it's sent to the server.
+#[derive(Debug, Deserialize, Serialize)]
+struct IpcMessage {
+    /// The shared secret, required over TCP.
+    token:   Option<String>,
+    request: IpcRequest,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+enum IpcRequest {
+    GetWatching,
+    GetDownloaded,
+    PollNow,
+    Cancel {
+        id:           Id,
+        keep_partial: bool,
+    },
+    Health,
+    Retry {
+        id: Id,
+    },
+    GetStats,
+    Reload,
+    GetLog {
+        lines: usize,
+    },
+    Check {
+        id: Id,
+    },
+    SetEnabled {
+        id:      Id,
+        enabled: bool,
+    },
+    /// The ids of the downloads that have a directory in the cache.
+    GetCachedIds,
+    UpdateYtDlp {
+        force: bool,
+    },
+    /// Keep the connection open and send `Event`s as JSON lines, instead of a
+    /// response.
+    Subscribe,
+}
+
+/// Something that happened to a download, sent to clients that made an
+/// `IpcRequest::Subscribe`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    Started {
+        id:   &'a Id,
+        info: &'a Info,
+    },
+    Progress {
+        id:       &'a Id,
+        progress: &'a DlProgress,
+    },
+    /// The download succeeded, failed, or was cancelled, as `info.outcome` says.
+    Finished {
+        id:   &'a Id,
+        info: &'a Info,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+enum IpcResponse {
+    Watching(Vec<Info>),
+    Downloaded(Vec<Info>),
+    Ok(String),
+    Error(String),
+    Unhealthy(String),
+    Stats(Stats),
+    Log(Vec<log::Record>),
+    Check(Check),
+    Ids(Vec<Id>),
+}
+
+/// What would be downloaded for an id right now.
+#[derive(Debug, Deserialize, Serialize)]
+struct Check {
+    id:       Id,
+    is_live:  bool,
+    /// Whether it's a scheduled stream that hasn't started yet.
+    upcoming: bool,
+    title:    Option<String>,
+    url:      Option<String>,
+    /// The file yt-dlp would download to, relative to the output directory.
+    filename: Option<String>,
+}
+
+/// Totals over this session's downloads.
+#[derive(Debug, Deserialize, Serialize)]
+struct Stats {
+    /// Bytes downloaded since vdl started, including by downloads in progress.
+    bytes_downloaded: u64,
+    active:           usize,
+    succeeded:        usize,
+    failed:           usize,
+    cancelled:        usize,
+    #[serde(default)]
+    skipped:          usize,
+    /// Bytes in the cache directory, where downloads are staged.
+    cache_bytes:      u64,
+}
+
+/// How many IPC connections can be handled at once.
+const MAX_IPC_CONNECTIONS: usize = 16;
+/// How many clients can subscribe to events at once. They stay connected, so they're
+/// counted separately from other connections.
+const MAX_IPC_SUBSCRIBERS: usize = 16;
+/// How often to send subscribers an empty line while there are no events, to notice
+/// the ones that have disconnected.
+const EVENTS_HEARTBEAT: Duration = Duration::from_secs(30);
+/// How long to wait for an IPC client to send its request, or read the response.
+const IPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Ipc {
+    inner_sub:    Arc<Mutex<InnerSub>>,
+    poll_now:     Arc<AtomicBool>,
+    listener:     UnixListener,
+    tcp_listener: Option<TcpListener>,
+    token:        Option<String>,
+    /// Where to reload the config from.
+    config_path:  PathBuf,
+    connections:  AtomicUsize,
+    subscribers:  AtomicUsize,
+}
+
+impl Ipc {
+    fn new(
+        inner_sub: Arc<Mutex<InnerSub>>,
+        poll_now: Arc<AtomicBool>,
+        tcp_addr: Option<&str>,
+        token: Option<String>,
+        config_path: PathBuf,
+    ) -> eyre::Result<Self> {
+        let runtime_dir = paths::runtime_dir();
+        let socket = paths::socket();
+
+        if !runtime_dir.exists() {
+            fs::create_dir_all(&runtime_dir)?;
+        }
+        if socket.exists() {
+            // Only remove it if it's left over from a server that's gone.
+            if UnixStream::connect(&socket).is_ok() {
+                return Err(eyre!(
+                    "Another instance is already running and listening on {socket:?}. \
+                     Use --instance to run more than one"
+                ));
+            }
+            fs::remove_file(&socket)
+                .map_err(|e| eyre!("Failed to remove stale socket {socket:?}: {e}"))?;
+        }
+        let tcp_listener = match tcp_addr {
+            Some(addr) => Some(
+                TcpListener::bind(addr)
+                    .map_err(|e| eyre!("Failed to bind IPC to {addr:?}: {e}"))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            inner_sub,
+            poll_now,
+            listener: UnixListener::bind(&socket)?,
+            tcp_listener,
+            token,
+            config_path,
+            connections: AtomicUsize::new(0),
+            subscribers: AtomicUsize::new(0),
+        })
+    }
+
+    fn spawn(self) -> eyre::Result<()> {
+        let ipc = Arc::new(self);
+        if ipc.tcp_listener.is_some() {
+            let ipc = ipc.clone();
+            std::thread::spawn(move || {
+                let listener = ipc.tcp_listener.as_ref().unwrap();
+                loop {
+                    let Ok((mut stream, addr)) = listener.accept() else {
+                        continue;
+                    };
+                    let _ = stream.set_read_timeout(Some(IPC_TIMEOUT));
+                    let _ = stream.set_write_timeout(Some(IPC_TIMEOUT));
+                    ipc.clone().handle_in_thread(move |ipc| {
+                        if let Err(e) = ipc.handle_stream(&mut stream, true) {
+                            log!(error, "IPC connection from {addr} failed: {e}");
+                        }
+                    });
+                }
+            });
+        }
+
+        loop {
+            let (mut stream, _sock_addr) = ipc.listener.accept()?;
+            let _ = stream.set_read_timeout(Some(IPC_TIMEOUT));
+            let _ = stream.set_write_timeout(Some(IPC_TIMEOUT));
+            ipc.clone().handle_in_thread(move |ipc| {
+                if let Err(e) = ipc.handle_stream(&mut stream, false) {
+                    log!(error, "IPC connection failed: {e}");
+                }
+            });
+        }
+    }
+
+    /// Handle a connection on its own thread, so a slow client can't hold up the
+    /// others. Connections over the limit are dropped.
+    fn handle_in_thread(self: Arc<Self>, handle: impl FnOnce(&Self) + Send + 'static) {
+        if self.connections.fetch_add(1, Ordering::Relaxed) >= MAX_IPC_CONNECTIONS {
+            self.connections.fetch_sub(1, Ordering::Relaxed);
+            log!(warn, "Dropping IPC connection: too many connections");
+            return;
+        }
+        std::thread::spawn(move || {
+            handle(&self);
+            self.connections.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    fn handle_stream(
+        &self,
+        stream: &mut (impl Read + Write),
+        require_token: bool,
+    ) -> eyre::Result<()> {
+        let mut message_body = Vec::new();
+        stream.read_to_end(&mut message_body)?;
+
+        let response = match serde_json::de::from_slice::<IpcMessage>(&message_body) {
+            Ok(message)
+                if require_token
+                    && !tokens_match(message.token.as_deref(), self.token.as_deref()) =>
+            {
+                IpcResponse::Error("Error: invalid token".to_string())
+            }
+            Ok(IpcMessage {
+                request: IpcRequest::Subscribe,
+                ..
+            }) => return self.subscribe(stream),
+            Ok(message) => self.handle_request(message.request),
+            Err(e) => {
+                IpcResponse::Error(format!("Error: failed to parse JSON request: {e}"))
+            }
+        };
+        let response_json = match serde_json::ser::to_vec(&response) {
+            Ok(x) => x,
+            Err(e) => serde_json::ser::to_vec(&IpcResponse::Error(format!(
+                "Error: failed to serialize response: {e}"
+            )))
+            .unwrap(),
+        };
+        stream.write_all(&response_json)?;
+        Ok(())
+    }
+
+    /// Stream events to a subscriber, moving it from the connection limit to the
+    /// subscriber limit for as long as it stays.
+    fn subscribe(&self, stream: &mut impl Write) -> eyre::Result<()> {
+        if self.subscribers.fetch_add(1, Ordering::Relaxed) >= MAX_IPC_SUBSCRIBERS {
+            self.subscribers.fetch_sub(1, Ordering::Relaxed);
+            let response = IpcResponse::Error("Error: too many subscribers".to_string());
+            stream.write_all(&serde_json::ser::to_vec(&response)?)?;
+            return Ok(());
+        }
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+        let result = Self::stream_events(stream);
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.fetch_sub(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Send events to `stream` as JSON lines until the client disconnects, with an
+    /// empty line every `EVENTS_HEARTBEAT` to notice that when it's quiet.
+    fn stream_events(stream: &mut impl Write) -> eyre::Result<()> {
+        let events = events::subscribe();
+        loop {
+            let line = match events.recv_timeout(EVENTS_HEARTBEAT) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => String::new(),
+                Err(RecvTimeoutError::Disconnected) => break,
             };
-            stream.write_all(&response_json)?;
+            if writeln!(stream, "{line}")
+                .and_then(|_| stream.flush())
+                .is_err()
+            {
+                break;
+            }
         }
+        Ok(())
     }
 
     fn handle_request(&self, req: IpcRequest) -> IpcResponse {
         match req {
+            IpcRequest::Subscribe => {
+                IpcResponse::Error("Error: can't subscribe to events here".to_string())
+            }
             IpcRequest::GetWatching => {
                 let inner = self.inner_sub.lock().unwrap();
                 let watching = inner
                     .watching
                     .values()
-                    .map(|x| x.info.clone())
+                    .map(|x| &x.info)
+                    .chain(inner.upcoming.values())
+                    .cloned()
                     .collect::<Vec<_>>();
 
                 IpcResponse::Watching(watching)
             }
+            IpcRequest::GetCachedIds => {
+                let inner = self.inner_sub.lock().unwrap();
+                let ids = inner.watching.keys().chain(inner.cancelled.keys());
+                IpcResponse::Ids(ids.cloned().collect())
+            }
             IpcRequest::GetDownloaded => {
                 let inner = self.inner_sub.lock().unwrap();
                 let x = inner.downloaded.values().cloned().collect::<Vec<_>>();
 
-                IpcResponse::Downloaded(x)
+                IpcResponse::Downloaded(x)
+            }
+            IpcRequest::Cancel { id, keep_partial } => {
+                let mut inner = self.inner_sub.lock().unwrap();
+                let Some(watching) = inner.watching.remove(&id) else {
+                    return IpcResponse::Error(format!(
+                        "Error: {id} isn't being downloaded"
+                    ));
+                };
+                watching.signal(libc::SIGTERM);
+                inner.cancelled.insert(id.clone(), (watching, keep_partial));
+                IpcResponse::Ok(format!("Cancelled {id}"))
+            }
+            IpcRequest::PollNow => {
+                self.poll_now.store(true, Ordering::Relaxed);
+                IpcResponse::Ok("Scheduled a poll for live streams".to_string())
+            }
+            IpcRequest::Retry { id } => {
+                let mut inner = self.inner_sub.lock().unwrap();
+                let Some(info) = inner.downloaded.get(&id) else {
+                    return IpcResponse::Error(format!(
+                        "Error: {id} hasn't been downloaded"
+                    ));
+                };
+                match &info.outcome {
+                    Some(Outcome::Succeeded) => {
+                        return IpcResponse::Error(format!(
+                            "Error: {id} was downloaded successfully"
+                        ));
+                    }
+                    Some(Outcome::Skipped { reason }) => {
+                        return IpcResponse::Error(format!(
+                            "Error: {id} was skipped: {reason}"
+                        ));
+                    }
+                    _ => {}
+                }
+                inner.retry.insert(id.clone());
+                self.poll_now.store(true, Ordering::Relaxed);
+                IpcResponse::Ok(format!("Retrying {id}"))
+            }
+            IpcRequest::Check {
+                id: id @ Id::Playlist { .. },
+            } => IpcResponse::Error(format!(
+                "Error: {id} is a playlist; check its videos instead"
+            )),
+            IpcRequest::Check { id } => {
+                let (mut yt_dlp, surface) = {
+                    let inner = self.inner_sub.lock().unwrap();
+                    let surface = inner.ids.get(&id).and_then(|x| x.live_surface);
+                    (inner.yt_dlp.clone(), surface.unwrap_or_default())
+                };
+                // Don't wait for scheduled streams to start.
+                yt_dlp.wait_for_video(None);
+                match check(&yt_dlp, id, surface) {
+                    Ok(check) => IpcResponse::Check(check),
+                    Err(e) => IpcResponse::Error(format!("Error: {e}")),
+                }
+            }
+            IpcRequest::SetEnabled { id, enabled } => {
+                let mut inner = self.inner_sub.lock().unwrap();
+                if !inner.ids.contains_key(&id) {
+                    return IpcResponse::Error(format!(
+                        "Error: {id} isn't subscribed to"
+                    ));
+                }
+                if enabled {
+                    inner.paused.remove(&id);
+                } else {
+                    inner.paused.insert(id.clone());
+                }
+                if let Err(e) = save_paused(&inner.paused) {
+                    return IpcResponse::Error(format!(
+                        "Error: couldn't save paused subscriptions: {e}"
+                    ));
+                }
+                let action = if enabled { "Resumed" } else { "Paused" };
+                IpcResponse::Ok(format!("{action} {id}"))
+            }
+            IpcRequest::GetLog { lines } => IpcResponse::Log(log::tail(lines)),
+            IpcRequest::UpdateYtDlp { .. } if YtDlp::is_external() => IpcResponse::Error(
+                format!("Error: {:?} isn't managed by vdl", YtDlp::exe_path()),
+            ),
+            IpcRequest::UpdateYtDlp { force } => {
+                let (agent, asset) = {
+                    let inner = self.inner_sub.lock().unwrap();
+                    (inner.update_agent.clone(), inner.yt_dlp_asset.clone())
+                };
+                let updated = agent
+                    .ok_or_else(|| eyre!("the config hasn't been loaded yet"))
+                    .and_then(|agent| {
+                        YtDlp::download_latest(&agent, asset.as_deref(), force)
+                    })
+                    .and_then(|()| YtDlp::installed_version());
+                match updated {
+                    Ok(version) => IpcResponse::Ok(format!(
+                        "yt-dlp {}",
+                        version.as_deref().unwrap_or("isn't installed")
+                    )),
+                    Err(e) => {
+                        IpcResponse::Error(format!("Error: failed to update yt-dlp: {e}"))
+                    }
+                }
+            }
+            IpcRequest::Reload => match reload(&self.config_path, &self.inner_sub) {
+                Ok(ids) => IpcResponse::Ok(format!("Reloaded config with {ids} ids")),
+                Err(e) => IpcResponse::Error(format!(
+                    "Error: failed to reload config (retaining previous config): {e}"
+                )),
+            },
+            IpcRequest::GetStats => {
+                let mut stats = {
+                    let inner = self.inner_sub.lock().unwrap();
+                    let count = |f: fn(&Outcome) -> bool| {
+                        inner
+                            .downloaded
+                            .values()
+                            .filter(|info| info.outcome.as_ref().is_some_and(f))
+                            .count()
+                    };
+                    Stats {
+                        bytes_downloaded: inner.bytes_downloaded
+                            + inner
+                                .watching
+                                .values()
+                                .map(Watching::downloaded_bytes)
+                                .sum::<u64>(),
+                        active:           inner.watching.len(),
+                        succeeded:        count(|x| matches!(x, Outcome::Succeeded)),
+                        failed:           count(|x| {
+                            matches!(
+                                x,
+                                Outcome::Failed { .. } | Outcome::Unverified { .. }
+                            )
+                        }),
+                        cancelled:        count(|x| matches!(x, Outcome::Cancelled)),
+                        skipped:          count(|x| matches!(x, Outcome::Skipped { .. })),
+                        cache_bytes:      0,
+                    }
+                };
+                // Walking the cache can take a while, so not while holding the lock.
+                stats.cache_bytes = dir_size(&paths::cache_dir());
+                IpcResponse::Stats(stats)
+            }
+            IpcRequest::Health => {
+                let mut inner = self.inner_sub.lock().unwrap();
+                let stall_timeout = inner.stall_timeout;
+                let stalled = inner
+                    .watching
+                    .iter_mut()
+                    .filter_map(|(id, watching)| {
+                        watching.update_progress();
+                        let stalled_for = watching.progress.1.elapsed();
+                        (stalled_for > stall_timeout).then(|| {
+                            format!("{id} has stalled for {}s", stalled_for.as_secs())
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                if stalled.is_empty() {
+                    let waiting = inner
+                        .watching
+                        .values()
+                        .filter(|x| !x.status.started.load(Ordering::Relaxed))
+                        .count();
+                    IpcResponse::Ok(format!(
+                        "Healthy: {} downloads in progress, {waiting} waiting to start",
+                        inner.watching.len() - waiting
+                    ))
+                } else {
+                    IpcResponse::Unhealthy(stalled.join("\n"))
+                }
+            }
+        }
+    }
+}
+
+/// Whether the token a client `sent` is the `expected` one. This takes just as long
+/// no matter how much of it is right, so a client can't guess it a byte at a time.
+fn tokens_match(sent: Option<&str>, expected: Option<&str>) -> bool {
+    let (Some(sent), Some(expected)) = (sent, expected) else {
+        return sent.is_none() && expected.is_none();
+    };
+    sent.len() == expected.len()
+        && sent
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Use this config file instead of `$XDG_CONFIG_HOME/vdl/config.toml`.
+    #[arg(long, global = true, value_name = "PATH")]
+    config:   Option<PathBuf>,
+    /// Run (or talk to) a separate, named instance with its own config file
+    /// (`$XDG_CONFIG_HOME/vdl/<name>.toml`), socket, and cache.
+    #[arg(long, global = true, value_name = "NAME", value_parser = parse_instance)]
+    instance: Option<String>,
+    #[command(subcommand)]
+    command:  Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Spawn the VOD downloading server.
+    Watch {
+        #[arg(short, long)]
+        silent: bool,
+    },
+
+    /// Communicate with the locally running VDL server.
+    Ipc {
+        /// Connect to a server over TCP (see `ipc_tcp_addr`) instead of the local socket.
+        #[arg(long, value_name = "HOST:PORT")]
+        addr:       Option<String>,
+        /// The server's `ipc_token`, required over TCP. Defaults to `$VDL_IPC_TOKEN`.
+        #[arg(long)]
+        token:      Option<String>,
+        /// Print lists of streams as JSON.
+        #[arg(long)]
+        json:       bool,
+        /// Only print errors; the exit code says whether the request succeeded.
+        #[arg(short, long, conflicts_with = "verbose")]
+        quiet:      bool,
+        /// Also print the server's raw response to stderr.
+        #[arg(short, long)]
+        verbose:    bool,
+        #[command(subcommand)]
+        subcommand: IpcCommand,
+    },
+    /// Write shell-completions and exit.
+    Completions {
+        /// Print completions for this shell, instead of the one from the environment.
+        #[arg(long, conflicts_with = "all")]
+        shell: Option<clap_complete::Shell>,
+        /// Write completions for every supported shell into this directory.
+        #[arg(long, value_name = "DIR")]
+        all:   Option<PathBuf>,
+    },
+    /// Print the version of vdl and the yt-dlp it uses.
+    YtDlpVersion,
+    /// Check that everything vdl needs is in place, and how to fix what isn't.
+    Doctor,
+    /// Remove download directories left in the cache, e.g. after a crash. Downloads a
+    /// running server is working on are kept.
+    Prune {
+        /// Only remove directories that haven't changed in this many days.
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+        /// Only print what would be removed.
+        #[arg(long)]
+        dry_run:    bool,
+    },
+    /// Inspect the configuration.
+    Config {
+        #[command(subcommand)]
+        subcommand: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Print the configuration as it will be used, with the defaults filled in.
+    Show,
+}
+
+#[derive(Debug, Subcommand)]
+enum IpcCommand {
+    /// Find out what streams the server is currently downloading, if any.
+    GetWatching,
+    /// Find out what streams the server has downloaded.
+    GetDownloaded,
+    /// Check for live streams now instead of waiting for the next poll.
+    Poll,
+    /// Exit with 2 if any download has stopped making progress.
+    Health,
+    /// Show how much has been downloaded, and how much space the cache is using.
+    Stats,
+    /// Reload the server's config file, like sending it a SIGHUP.
+    Reload,
+    /// Find out whether an id is live and what would be downloaded, without
+    /// subscribing to it.
+    Check {
+        /// The id to check, e.g. `yt:@PiscosHour`.
+        id: Id,
+    },
+    /// Stop checking a subscription for live streams until it's resumed. Downloads
+    /// in progress carry on.
+    Pause {
+        /// The subscribed id, e.g. `yt:@PiscosHour`.
+        id: Id,
+    },
+    /// Start checking a paused subscription again.
+    Resume {
+        /// The subscribed id, e.g. `yt:@PiscosHour`.
+        id: Id,
+    },
+    /// Update the server's yt-dlp to the latest release, e.g. for an extractor fix.
+    /// Downloads in progress keep the version they started with.
+    UpdateYtDlp {
+        /// Download it again even if it's already the latest version.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Print events about downloads as JSON lines as they happen: when they start,
+    /// their progress, and when they finish.
+    Events,
+    /// Print the server's recent log lines.
+    Log {
+        /// How many lines to print.
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
+    /// Try a failed download again.
+    Retry {
+        /// The id of the download, e.g. `yt:dQw4w9WgXcQ`.
+        id: Id,
+    },
+    /// Stop downloading a stream.
+    Cancel {
+        /// The id of the download, e.g. `yt:dQw4w9WgXcQ`.
+        id:           Id,
+        /// Keep the partially downloaded files, in the same place as `keep_staging`.
+        #[arg(short, long)]
+        keep_partial: bool,
+    },
+}
+
+fn main() -> eyre::Result<()> {
+    let args = Args::parse();
+    if let Some(instance) = args.instance {
+        paths::set_instance(instance);
+    }
+    match args.command {
+        Command::Watch { silent } => serve(silent, args.config),
+        Command::Ipc {
+            addr,
+            token,
+            json,
+            quiet,
+            verbose,
+            subcommand,
+        } => ipc(subcommand, addr, token, json, quiet, verbose),
+        Command::Config {
+            subcommand: ConfigCommand::Show,
+        } => {
+            let config_path = args.config.unwrap_or_else(paths::config_file);
+            let config = Config::load(&config_path)?;
+            print!("{}", basic_toml::to_string(&config.resolved())?);
+            Ok(())
+        }
+        Command::YtDlpVersion => {
+            println!("{NAME} {}", env!("CARGO_PKG_VERSION"));
+            let exe_path = YtDlp::exe_path();
+            match YtDlp::installed_version()? {
+                Some(version) => println!("yt-dlp {version} ({exe_path:?})"),
+                None => println!(
+                    "yt-dlp isn't installed at {exe_path:?} yet; it's downloaded by `{NAME} watch`"
+                ),
+            }
+            Ok(())
+        }
+        Command::Doctor => doctor(&args.config.unwrap_or_else(paths::config_file)),
+        Command::Prune {
+            older_than,
+            dry_run,
+        } => prune(older_than, dry_run),
+        Command::Completions { shell, all } => {
+            use clap::{CommandFactory, ValueEnum};
+            use clap_complete::Shell;
+            let mut cmd = Args::command();
+            if let Some(dir) = all {
+                fs::create_dir_all(&dir)?;
+                for &shell in Shell::value_variants() {
+                    let path = clap_complete::generate_to(shell, &mut cmd, NAME, &dir)?;
+                    eprintln!("Wrote {shell} completions to {path:?}");
+                }
+                return Ok(());
+            }
+            if let Some(shell) = shell {
+                clap_complete::generate(shell, &mut cmd, NAME, &mut std::io::stdout());
+                return Ok(());
+            }
+            let shell = Shell::from_env()
+                .ok_or_else(|| eyre!("Couldn't determine shell from environment!"))?;
+
+            match shell {
+                Shell::Fish => {
+                    let vendor_completions_dir = dirs::data_dir()
+                        .expect("data dir")
+                        .join("fish/vendor_completions.d");
+                    let vendor_completions_path = vendor_completions_dir.join("vdl.fish");
+                    if !vendor_completions_dir.exists() {
+                        fs::create_dir_all(&vendor_completions_dir)?;
+                    }
+                    let mut f = File::create(&vendor_completions_path)?;
+                    eprintln!("Writing completions to {vendor_completions_path:?}");
+                    clap_complete::generate(shell, &mut cmd, NAME, &mut f);
+                }
+                _ => {
+                    clap_complete::generate(
+                        shell,
+                        &mut cmd,
+                        NAME,
+                        &mut std::io::stdout(),
+                    );
+                }
+            };
+            Ok(())
+        }
+    }
+}
+
+fn parse_instance(name: &str) -> Result<String, String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(
+            "instance names may only contain letters, digits, '-' and '_'".into(),
+        );
+    }
+    Ok(name.to_string())
+}
+
+fn doctor(config_path: &Path) -> eyre::Result<()> {
+    let color = use_color();
+    let mut failures = 0;
+    let mut report = |name: &str, result: Result<String, String>, hint: &str| {
+        let (mark, style, detail) = match result {
+            Ok(detail) => ("ok", GREEN, detail),
+            Err(detail) => {
+                failures += 1;
+                ("FAIL", RED, format!("{detail}\n       {hint}"))
+            }
+        };
+        let mark = if color {
+            format!("\x1b[{style}m{mark:4}\x1b[0m")
+        } else {
+            format!("{mark:4}")
+        };
+        println!("{mark} {name:9} {detail}");
+    };
+
+    // Parsed and validated separately, so the other checks can go ahead with what's
+    // there even if it isn't valid.
+    let config = match fs::read_to_string(config_path) {
+        Ok(toml) => basic_toml::from_str::<Config>(&toml).map_err(|e| e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e.to_string()),
+    };
+    report(
+        "config",
+        config
+            .as_ref()
+            .map_err(ToString::to_string)
+            .and_then(|x| x.validate().map_err(|e| e.to_string()))
+            .map(|()| format!("{config_path:?}")),
+        "Fix the config file; see the README for the options",
+    );
+    let config = config.unwrap_or_default();
+
+    let exe_path = YtDlp::exe_path();
+    let (result, hint) = match YtDlp::installed_version() {
+        Ok(Some(version)) => (Ok(format!("{version} ({exe_path:?})")), ""),
+        Ok(None) => (
+            Err(format!("not installed at {exe_path:?}")),
+            "Run `vdl watch` to download it",
+        ),
+        Err(e) => (
+            Err(format!("{exe_path:?} couldn't be run: {e}")),
+            "Delete it, and `vdl watch` will download it again",
+        ),
+    };
+    let hint = if YtDlp::is_external() {
+        "Fix VDL_YT_DLP_PATH, or unset it to let vdl download yt-dlp"
+    } else {
+        hint
+    };
+    report("yt-dlp", result, hint);
+
+    let runs = |program: &str| {
+        std::process::Command::new(program)
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|_| "on the PATH".to_string())
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => "not on the PATH".to_string(),
+                _ => e.to_string(),
+            })
+    };
+    report(
+        "ffmpeg",
+        runs("ffmpeg"),
+        "Install ffmpeg; yt-dlp needs it to merge formats, remux, and embed",
+    );
+    if config.verify {
+        report(
+            "ffprobe",
+            runs("ffprobe"),
+            "Install ffmpeg, which comes with ffprobe, or turn off verify",
+        );
+    }
+
+    let dl_options = config.dl_options();
+    let dirs = std::iter::once(dl_options.out_dir).chain(
+        config
+            .ids
+            .iter()
+            .filter_map(|sub| sub.options.output_dir.clone().map(absolute)),
+    );
+    for dir in dirs.collect::<std::collections::BTreeSet<_>>() {
+        report(
+            "output",
+            check_output_dir(&dir)
+                .map(|()| format!("{dir:?}"))
+                .map_err(|e| format!("{dir:?}: {e}")),
+            "Create the directory, or make it writable",
+        );
+    }
+
+    if config.cookies_files.is_empty() {
+        let cookies = config.cookies_from_browser.as_deref().unwrap_or("firefox");
+        report(
+            "cookies",
+            yt_dlp::check_cookies_from_browser(cookies)
+                .map(|()| cookies.to_string())
+                .map_err(|e| format!("{cookies:?}: {e}")),
+            "Set cookies_from_browser to a browser you're logged in to YouTube with",
+        );
+    }
+    for file in &config.cookies_files {
+        report(
+            "cookies",
+            match file.is_file() {
+                true => Ok(format!("{file:?}")),
+                false => Err(format!("{file:?} doesn't exist")),
+            },
+            "Export the account's cookies to the file, or remove it from cookies_files",
+        );
+    }
+
+    report(
+        "github",
+        YtDlp::get_latest_tag(&config.update_agent())
+            .map(|tag| format!("the latest yt-dlp is {tag}"))
+            .map_err(|e| e.to_string()),
+        "Check the network connection; yt-dlp is updated from GitHub",
+    );
+
+    if failures > 0 {
+        return Err(eyre!("{failures} of the checks failed"));
+    }
+    Ok(())
+}
+
+fn prune(older_than: Option<u64>, dry_run: bool) -> eyre::Result<()> {
+    // If nothing is listening, e.g. after a crash, nothing is being downloaded.
+    let in_use = match UnixStream::connect(paths::socket()) {
+        Ok(mut stream) => {
+            let message = IpcMessage {
+                token:   None,
+                request: IpcRequest::GetCachedIds,
+            };
+            stream.write_all(&serde_json::to_vec(&message)?)?;
+            stream.shutdown(Shutdown::Write)?;
+            match serde_json::from_reader(stream)? {
+                IpcResponse::Ids(ids) => ids.iter().map(Id::to_string).collect(),
+                IpcResponse::Error(e) => return Err(eyre!("{e}")),
+                x => return Err(eyre!("Unexpected response from the server: {x:?}")),
+            }
+        }
+        Err(_) => HashSet::new(),
+    };
+    let min_age = Duration::from_secs(older_than.unwrap_or(0) * 24 * 60 * 60);
+
+    let mut freed = 0;
+    let cache_dir = paths::cache_dir();
+    for entry in fs::read_dir(&cache_dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if in_use.contains(&name) {
+            eprintln!("Keeping {path:?}: it's being downloaded");
+            continue;
+        }
+        if last_modified(&path).elapsed().unwrap_or_default() < min_age {
+            continue;
+        }
+        let size = dir_size(&path);
+        if dry_run {
+            println!("Would remove {path:?} ({})", format_bytes(size as f64));
+        } else {
+            fs::remove_dir_all(&path).map_err(|e| eyre!("{path:?}: {e}"))?;
+            println!("Removed {path:?} ({})", format_bytes(size as f64));
+        }
+        freed += size;
+    }
+    let verb = if dry_run { "Would free" } else { "Freed" };
+    println!("{verb} {}", format_bytes(freed as f64));
+    Ok(())
+}
+
+/// When `dir` or anything directly in it was last modified.
+fn last_modified(dir: &Path) -> std::time::SystemTime {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .chain(std::iter::once(dir.to_path_buf()))
+        .filter_map(|path| fs::metadata(path).ok()?.modified().ok())
+        .max()
+        .unwrap_or(std::time::UNIX_EPOCH)
+}
+
+/// Print the events the server sends until it goes away.
+fn print_events(stream: impl Read) -> eyre::Result<()> {
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        // Heartbeats.
+        if line.is_empty() {
+            continue;
+        }
+        // The request can still be refused, e.g. for a bad token.
+        if let Ok(IpcResponse::Error(e)) = serde_json::from_str(&line) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn ipc(
+    command: IpcCommand,
+    addr: Option<String>,
+    token: Option<String>,
+    json: bool,
+    quiet: bool,
+    verbose: bool,
+) -> eyre::Result<()> {
+    let request = match command {
+        IpcCommand::GetWatching => IpcRequest::GetWatching,
+        IpcCommand::GetDownloaded => IpcRequest::GetDownloaded,
+        IpcCommand::Poll => IpcRequest::PollNow,
+        IpcCommand::Health => IpcRequest::Health,
+        IpcCommand::Stats => IpcRequest::GetStats,
+        IpcCommand::Reload => IpcRequest::Reload,
+        IpcCommand::Log { lines } => IpcRequest::GetLog { lines },
+        IpcCommand::Events => IpcRequest::Subscribe,
+        IpcCommand::Check { id } => IpcRequest::Check { id },
+        IpcCommand::Pause { id } => IpcRequest::SetEnabled { id, enabled: false },
+        IpcCommand::Resume { id } => IpcRequest::SetEnabled { id, enabled: true },
+        IpcCommand::UpdateYtDlp { force } => IpcRequest::UpdateYtDlp { force },
+        IpcCommand::Retry { id } => IpcRequest::Retry { id },
+        IpcCommand::Cancel { id, keep_partial } => {
+            IpcRequest::Cancel { id, keep_partial }
+        }
+    };
+    let message = IpcMessage {
+        token: token.or_else(|| std::env::var("VDL_IPC_TOKEN").ok()),
+        request,
+    };
+    let request_json = serde_json::ser::to_vec(&message)?;
+    let subscribe = matches!(message.request, IpcRequest::Subscribe);
+
+    let mut response_json = Vec::new();
+    if let Some(addr) = addr {
+        let mut stream = TcpStream::connect(&addr)
+            .map_err(|e| eyre!("Couldn't connect to {addr:?}: {e}"))?;
+        stream.write_all(&request_json)?;
+        stream.shutdown(Shutdown::Write)?;
+        if subscribe {
+            return print_events(stream);
+        }
+        stream.read_to_end(&mut response_json)?;
+    } else {
+        let socket = paths::socket();
+        let mut stream = UnixStream::connect(&socket).map_err(|e| {
+            eyre!(
+                "Couldn't connect to socket {socket:?} (ensure an instance is running): {e}"
+            )
+        })?;
+        stream.write_all(&request_json)?;
+        stream.shutdown(Shutdown::Write)?;
+        if subscribe {
+            return print_events(stream);
+        }
+        stream.read_to_end(&mut response_json)?;
+    }
+
+    if verbose {
+        eprintln!("{}", String::from_utf8_lossy(&response_json));
+    }
+    let response: IpcResponse = serde_json::de::from_slice(&response_json)?;
+    match response {
+        IpcResponse::Error(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        IpcResponse::Unhealthy(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+        _ if quiet => {}
+        IpcResponse::Watching(watching) => print_infos("Watching", &watching, json)?,
+        IpcResponse::Downloaded(downloaded) => {
+            print_infos("Downloaded", &downloaded, json)?
+        }
+        IpcResponse::Ok(message) => println!("{message}"),
+        IpcResponse::Check(check) if json => {
+            println!("{}", serde_json::to_string_pretty(&check)?)
+        }
+        IpcResponse::Check(check) => {
+            let status = match (check.is_live, check.upcoming) {
+                (true, _) => "is live",
+                (false, true) => "is scheduled",
+                (false, false) => "isn't live",
+            };
+            println!("{} {status}", check.id);
+            for (label, value) in [
+                ("Title", &check.title),
+                ("URL", &check.url),
+                ("Filename", &check.filename),
+            ] {
+                if let Some(value) = value {
+                    println!("{label:9} {value}");
+                }
+            }
+        }
+        IpcResponse::Log(lines) if json => {
+            println!("{}", serde_json::to_string_pretty(&lines)?)
+        }
+        IpcResponse::Log(lines) => lines.iter().for_each(|line| println!("{line}")),
+        IpcResponse::Ids(ids) => ids.iter().for_each(|id| println!("{id}")),
+        IpcResponse::Stats(stats) if json => {
+            println!("{}", serde_json::to_string_pretty(&stats)?)
+        }
+        IpcResponse::Stats(stats) => {
+            println!(
+                "Downloaded {} this session",
+                format_bytes(stats.bytes_downloaded as f64)
+            );
+            println!(
+                "{} downloading, {} downloaded, {} failed, {} cancelled, {} skipped",
+                stats.active,
+                stats.succeeded,
+                stats.failed,
+                stats.cancelled,
+                stats.skipped
+            );
+            println!("Cache is using {}", format_bytes(stats.cache_bytes as f64));
+        }
+    }
+    Ok(())
+}
+
+/// Print `infos` as a table under a `title` like "Watching", or as a JSON array.
+fn print_infos(title: &str, infos: &[Info], json: bool) -> eyre::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(infos)?);
+        return Ok(());
+    }
+
+    println!("{title} {} streams", infos.len());
+    if infos.is_empty() {
+        return Ok(());
+    }
+    let now = unix_now();
+    let starts = infos
+        .iter()
+        .map(|info| match info.release_timestamp {
+            Some(start) if start > now => {
+                let mins = (start - now + 59) / 60;
+                format!("in {}h{:02}m", mins / 60, mins % 60)
+            }
+            _ => String::new(),
+        })
+        .collect::<Vec<_>>();
+    // Only scheduled streams have a start time, so the column is usually empty.
+    let show_starts = starts.iter().any(|x| !x.is_empty());
+    let statuses = infos
+        .iter()
+        .map(|info| info.outcome.as_ref().map(ToString::to_string))
+        .collect::<Vec<_>>();
+    let show_status = statuses.iter().any(Option::is_some);
+    let took = infos
+        .iter()
+        .map(|info| info.elapsed().map(format_duration))
+        .collect::<Vec<_>>();
+    let show_took = took.iter().any(Option::is_some);
+    let subscriptions = infos
+        .iter()
+        .map(|info| info.subscription.as_ref().map(ToString::to_string))
+        .collect::<Vec<_>>();
+    let show_subscriptions = subscriptions.iter().any(Option::is_some);
+
+    let mut header = vec!["TITLE", "UPLOADER", "URL"];
+    if show_subscriptions {
+        header.push("SUBSCRIPTION");
+    }
+    if show_starts {
+        header.push("STARTS");
+    }
+    if show_status {
+        header.push("STATUS");
+    }
+    if show_took {
+        header.push("TOOK");
+    }
+    let rows = infos
+        .iter()
+        .zip(&starts)
+        .zip(&statuses)
+        .zip(&took)
+        .zip(&subscriptions)
+        .map(|((((info, starts), status), took), subscription)| {
+            let mut row = vec![info.title.as_str(), &info.uploader, &info.webpage_url];
+            if show_subscriptions {
+                row.push(subscription.as_deref().unwrap_or_default());
+            }
+            if show_starts {
+                row.push(starts);
+            }
+            if show_status {
+                row.push(status.as_deref().unwrap_or_default());
+            }
+            if show_took {
+                row.push(took.as_deref().unwrap_or_default());
+            }
+            row
+        })
+        .collect::<Vec<_>>();
+    let mut widths = header.iter().map(|x| x.chars().count()).collect::<Vec<_>>();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let colors = infos.iter().map(|info| match info.outcome {
+        Some(Outcome::Succeeded) => GREEN,
+        Some(Outcome::Failed { .. } | Outcome::Unverified { .. }) => RED,
+        Some(Outcome::Cancelled | Outcome::Skipped { .. }) => DIM,
+        None => YELLOW,
+    });
+    let color = use_color();
+    for (row, style) in std::iter::once((&header, BOLD)).chain(rows.iter().zip(colors)) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let line = line.trim_end();
+        if color {
+            println!("\x1b[{style}m{line}\x1b[0m");
+        } else {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+// ANSI SGR parameters for `print_infos`.
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+/// Whether to color output: only when stdout is a terminal, and `NO_COLOR` isn't set.
+fn use_color() -> bool {
+    std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none_or(|x| x.is_empty())
+}
+
+/// Make sure this is the only server for this instance by locking the lock file,
+/// which the OS releases when the process exits however it does.
+fn lock_instance() -> eyre::Result<File> {
+    let path = paths::lock_file();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|e| eyre!("Failed to open lock file {path:?}: {e}"))?;
+    if file.try_lock().is_err() {
+        let mut pid = String::new();
+        let _ = file.read_to_string(&mut pid);
+        return Err(eyre!(
+            "Another instance is already running (PID {}). Use --instance to run more \
+             than one",
+            pid.trim()
+        ));
+    }
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+    Ok(file)
+}
+
+fn serve(silent: bool, config_path: Option<PathBuf>) -> eyre::Result<()> {
+    let reload_config = Arc::new(AtomicBool::new(false));
+    let exit = Arc::new(AtomicBool::new(false));
+    let dump_state = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGHUP, reload_config.clone()).unwrap();
+    signal_hook::flag::register(SIGUSR1, dump_state.clone()).unwrap();
+    signal_hook::flag::register(SIGTERM, exit.clone()).unwrap();
+    // Ctrl-C in a terminal sends SIGINT to the whole process group, so the yt-dlp
+    // children get it too and stop on their own.
+    signal_hook::flag::register(SIGINT, exit.clone()).unwrap();
+
+    let _lock = lock_instance()?;
+    let config_path = config_path.unwrap_or_else(paths::config_file);
+    let config = Config::load(&config_path)?;
+
+    let subscriber = Subscriber::default();
+    let inner = subscriber.inner.clone();
+    {
+        let mut inner = inner.lock().unwrap();
+        inner.apply_config(&config);
+        inner.paused = load_paused();
+    }
+
+    let ipc = Ipc::new(
+        inner.clone(),
+        subscriber.poll_now.clone(),
+        config.ipc_tcp_addr.as_deref(),
+        config.ipc_token.clone(),
+        config_path.clone(),
+    )?;
+    std::thread::spawn(move || ipc.spawn());
+
+    YtDlp::download_latest(
+        &config.update_agent(),
+        config.yt_dlp_asset.as_deref(),
+        false,
+    )?;
+
+    let subscriber = std::thread::spawn(move || subscriber.spawn(silent));
+
+    loop {
+        if subscriber.is_finished() {
+            log!(error, "Subscriber exited!");
+            subscriber.join().unwrap()?;
+            std::process::exit(1);
+        }
+        if exit.swap(false, Ordering::Relaxed) {
+            stop_downloads(&inner.lock().unwrap());
+            let _ = fs::remove_file(paths::socket());
+            let _ = fs::remove_file(paths::lock_file());
+            std::process::exit(1);
+        }
+        // Don't wait on the lock, in case it's what's stuck; try again next time.
+        if dump_state.load(Ordering::Relaxed)
+            && let Ok(inner) = inner.try_lock()
+        {
+            dump_state.store(false, Ordering::Relaxed);
+            inner.log_state();
+        }
+        if reload_config.swap(false, Ordering::Relaxed)
+            && let Err(e) = reload(&config_path, &inner)
+        {
+            log!(
+                error,
+                "Failed to reload config (retaining previous config): {e}"
+            )
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+}
+
+/// Find out what would be downloaded for `id`, without downloading anything.
+fn check(yt_dlp: &YtDlp, id: Id, surface: LiveSurface) -> error::Result<Check> {
+    let mut check = Check {
+        id,
+        is_live: false,
+        upcoming: false,
+        title: None,
+        url: None,
+        filename: None,
+    };
+    match &check.id {
+        Id::Yt { yt_id } => {
+            let Some(info) = live_info(yt_dlp, yt_id, surface)? else {
+                return Ok(check);
+            };
+            check.is_live = info.is_live;
+            check.upcoming = info.is_upcoming();
+            check.title = Some(info.title);
+            check.url = Some(info.webpage_url);
+        }
+        Id::Twitch { twitch_id } => {
+            if !twitch_is_live(yt_dlp, twitch_id) {
+                return Ok(check);
+            }
+            let info = Info::get(yt_dlp, &check.id)?;
+            check.is_live = true;
+            check.title = Some(info.title);
+            check.url = Some(info.webpage_url);
+        }
+        // Playlists are never downloaded themselves.
+        Id::Playlist { .. } => return Ok(check),
+    }
+    if let Some(url) = &check.url {
+        let output = yt_dlp
+            .command_with_args()
+            .args([url, "--ignore-no-formats-error", "--print", "filename"])
+            .output()?;
+        let filename = String::from_utf8(output.stdout)?;
+        check.filename = Some(filename.trim().to_string()).filter(|x| !x.is_empty());
+    }
+    Ok(check)
+}
+
+/// Load the config at `path` again and apply it, returning how many ids it has. If it
+/// can't be loaded, the previous config stays in use.
+fn reload(path: &Path, inner: &Mutex<InnerSub>) -> eyre::Result<usize> {
+    log!("Reloading config..");
+    let config = Config::load(path)?;
+    inner.lock().unwrap().apply_config(&config);
+    log!("Reloaded config!");
+    Ok(config.ids.len())
+}
+
+/// Record the downloads that have finished, and stop watching them.
+fn finish_downloads(
+    inner: &mut InnerSub,
+    progress_bars: &mut HashMap<Id, ProgressBar>,
+    silent: bool,
+) {
+    let mut remove = vec![];
+    for (id, task) in inner.watching.iter() {
+        if task.thread.is_finished() {
+            remove.push(id.clone());
+        }
+    }
+
+    for r in remove {
+        let Some(watched) = inner.watching.remove(&r) else {
+            log!(warn, "{r} finished, but it's no longer being watched");
+            continue;
+        };
+        inner.bytes_downloaded += watched.downloaded_bytes();
+        let ret = watched.thread.join().unwrap_or_else(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown cause");
+            Err(eyre!("Download thread panicked: {message}"))
+        });
+        let ret = match ret {
+            Err(_) if watched.timed_out.is_some() => {
+                Err(eyre!("it was stopped for going over download_timeout_secs"))
+            }
+            ret => ret,
+        };
+        let error = ret.as_ref().err().map(ToString::to_string);
+        let skipped = ret.as_ref().err().and_then(|e| match e.downcast_ref() {
+            Some(error::Error::Skipped(reason)) => Some(reason.clone()),
+            _ => None,
+        });
+        // Skipping it was deliberate, so it's not worth a notification.
+        if skipped.is_none() {
+            inner
+                .notifier
+                .download_finished(&watched.info, error.as_deref());
+        }
+        let unverified = ret.as_ref().is_err_and(|e| {
+            matches!(e.downcast_ref(), Some(error::Error::Unverified(_)))
+        });
+        let mut info = watched.info;
+        info.finished_at = Some(unix_now());
+        let took = format_duration(info.elapsed().unwrap_or_default());
+        let (level, message) = match ret.as_ref().map_err(|e| (e, e.downcast_ref())) {
+            Ok(_) => (
+                log::Level::Info,
+                format!("Downloaded {:?} - {} in {took}", info.title, info.uploader),
+            ),
+            Err((_, Some(error::Error::Skipped(reason)))) => (
+                log::Level::Info,
+                format!("Skipped {:?} - {}: {reason}", info.title, info.uploader),
+            ),
+            Err((e, Some(error::Error::Unverified(_)))) => (
+                log::Level::Warn,
+                format!(
+                    "Downloaded {:?} - {} in {took}, but it's damaged: {e}",
+                    info.title, info.uploader
+                ),
+            ),
+            Err((e, Some(error::Error::SizeLimit { .. }))) => (
+                log::Level::Warn,
+                format!(
+                    "Cut {:?} - {} short after {took}: {e}",
+                    info.title, info.uploader
+                ),
+            ),
+            // Its staging files are left in the cache, so trying again resumes it.
+            Err((e, Some(error::Error::Killed { .. }))) => (
+                log::Level::Warn,
+                format!(
+                    "Stopped {:?} - {} after {took}, resumable with `vdl retry`: {e}",
+                    info.title, info.uploader
+                ),
+            ),
+            Err((e, _)) => (
+                log::Level::Error,
+                format!(
+                    "Failed to download {:?} - {} after {took}: {e}",
+                    info.title, info.uploader
+                ),
+            ),
+        };
+        let kept = kept_staging_dir(&watched.dl_dir, &info);
+        let message = if kept.exists() {
+            format!("{message} (staging files kept in {kept:?})")
+        } else {
+            message
+        };
+        log::record(level, message.clone());
+        match progress_bars.remove(&r) {
+            Some(pb) => pb.finish_with_message(message),
+            None if !silent => eprintln!("{r} has no progress bar: {message}"),
+            None => {}
+        }
+        info.outcome = Some(match error {
+            Some(_) if let Some(reason) = skipped => Outcome::Skipped { reason },
+            Some(error) if unverified => Outcome::Unverified { error },
+            Some(error) => Outcome::Failed { error },
+            None => Outcome::Succeeded,
+        });
+        events::send(&Event::Finished {
+            id:   &r,
+            info: &info,
+        });
+        inner.downloaded.insert(r, info);
+    }
+}
+
+/// Ask every running yt-dlp process, and whatever was started alongside it, to stop,
+/// and kill the ones that don't within a few seconds.
+fn stop_downloads(inner: &InnerSub) {
+    inner
+        .watching
+        .values()
+        .for_each(|x| x.signal(libc::SIGTERM));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline
+        && inner.watching.values().any(|x| !x.thread.is_finished())
+    {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    inner
+        .watching
+        .values()
+        .for_each(|x| x.signal(libc::SIGKILL));
+}
+
+fn live_info(
+    yt_dlp: &YtDlp,
+    id: &str,
+    surface: LiveSurface,
+) -> error::Result<Option<YtLiveInfo>> {
+    let url = match surface {
+        LiveSurface::Live => format!("https://www.youtube.com/{id}/live"),
+        LiveSurface::Streams => match live_stream_on_tab(yt_dlp, id)? {
+            Some(video_id) => format!("https://www.youtube.com/watch?v={video_id}"),
+            None => return Ok(None),
+        },
+    };
+    let Some(stdout) = fetch_json(yt_dlp, &url)? else {
+        return Ok(None);
+    };
+    let info: YtLiveInfo = serde_json::from_str(&stdout)?;
+    Ok(Some(info))
+}
+
+/// The video id of the newest live or upcoming stream on channel `id`'s Streams tab.
+fn live_stream_on_tab(yt_dlp: &YtDlp, id: &str) -> error::Result<Option<String>> {
+    /// How many of the newest entries to look through.
+    const ENTRIES: &str = "1:5";
+
+    #[derive(Deserialize)]
+    struct Tab {
+        #[serde(default)]
+        entries: Vec<Entry>,
+    }
+    #[derive(Deserialize)]
+    struct Entry {
+        id:          String,
+        live_status: Option<LiveStatus>,
+    }
+
+    let mut yt_dlp = yt_dlp.clone();
+    let mut extra_args = yt_dlp.extra_args.clone();
+    extra_args.extend(["--flat-playlist", "--playlist-items", ENTRIES].map(String::from));
+    yt_dlp.extra_args(&extra_args);
+    let url = format!("https://www.youtube.com/{id}/streams");
+    let Some(stdout) = fetch_json(&yt_dlp, &url)? else {
+        return Ok(None);
+    };
+    let tab: Tab = serde_json::from_str(&stdout)?;
+    Ok(tab
+        .entries
+        .into_iter()
+        .find(|x| {
+            matches!(
+                x.live_status,
+                Some(LiveStatus::IsLive | LiveStatus::IsUpcoming)
+            )
+        })
+        .map(|x| x.id))
+}
+
+/// How long yt-dlp gets to stop after a `download_timeout_secs` before it's killed.
+const TIMEOUT_GRACE: Duration = Duration::from_secs(30);
+
+/// How many times to retry `yt-dlp -J` after a transient failure.
+const FETCH_RETRIES: u32 = 3;
+
+/// Get the info json for `url` with `yt-dlp -J`, or `None` if there's nothing there
+/// (e.g. the channel isn't live). Failures that look transient, like rate limiting or
+/// network errors, are retried with a backoff.
+fn fetch_json(yt_dlp: &YtDlp, url: &str) -> error::Result<Option<String>> {
+    // Lowercase fragments of yt-dlp errors that are worth trying again after.
+    const TRANSIENT: &[&str] = &[
+        "http error 429",
+        "http error 500",
+        "http error 501",
+        "http error 502",
+        "http error 503",
+        "http error 504",
+        "timed out",
+        "connection reset",
+        "connection refused",
+        "temporary failure in name resolution",
+    ];
+
+    let mut backoff = Duration::from_secs(1);
+    for attempt in 0..=FETCH_RETRIES {
+        let output = yt_dlp
+            .command_with_args()
+            // Otherwise there's no info for streams that haven't started yet.
+            .args(["-J", "--ignore-no-formats-error", url])
+            .output()?;
+        let stdout = String::from_utf8(output.stdout)?;
+        if !stdout.trim().is_empty() {
+            return Ok(Some(stdout));
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+        if stderr.contains("members-only") || stderr.contains("join this channel") {
+            return Err(error::Error::MembersOnly {
+                url: url.to_string(),
+            });
+        }
+        if !TRANSIENT.iter().any(|x| stderr.contains(x)) {
+            break;
+        }
+        if attempt == FETCH_RETRIES {
+            let last = stderr.lines().rfind(|x| !x.trim().is_empty());
+            return Err(error::Error::Network(last.unwrap_or_default().to_string()));
+        }
+        std::thread::sleep(backoff);
+        backoff *= 2;
+    }
+    Ok(None)
+}
+
+fn dl(
+    yt_dlp: &YtDlp,
+    options: &DlOptions,
+    info: &Info,
+    url: &str,
+    dl_dir: PathBuf,
+    status: &DlStatus,
+) -> error::Result<()> {
+    if let Some(command) = &options.pipe_to {
+        return pipe_dl(yt_dlp, url, command, &dl_dir, status);
+    }
+    let mut out_dir = options.out_dir.clone();
+    if options.organize_by_uploader {
+        out_dir.push(sanitize_filename(&info.uploader));
+    }
+    // This has to use the same arguments as the download itself (e.g. the file name
+    // restrictions), or the predicted file name won't match. With `wait_for_video`, this
+    // is also where we wait for a scheduled stream to start, so it has to be killable.
+    let child = yt_dlp
+        .command_with_args()
+        .arg(url)
+        .arg("--print")
+        .arg(format!("{FILENAME_PREFIX}%(filename)s"))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    status.pid.store(child.id(), Ordering::Relaxed);
+    let output = child.wait_with_output();
+    status.pid.store(0, Ordering::Relaxed);
+    let output = output?;
+    if let Some(signal) = output.status.signal() {
+        return Err(error::Error::Killed { signal });
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    // Anything else yt-dlp prints, like warnings, is ignored.
+    let filename = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix(FILENAME_PREFIX))
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .ok_or_else(|| {
+            error::Error::Parse("yt-dlp didn't print a filename".to_string())
+        })?;
+    // yt-dlp already accounts for `merge_output_format` in the name it prints, but not
+    // for remuxing, which comes after.
+    let mut output_filename = PathBuf::from(filename);
+    if let Some(remux_video) = yt_dlp.remux_video.as_deref() {
+        let source = output_filename
+            .extension()
+            .map(|x| x.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if let Some(format) = remux_target(remux_video, &source) {
+            output_filename.set_extension(format);
+        }
+    }
+    let final_out = out_dir.join(&output_filename);
+    let tmp_out_path = dl_dir.join(&output_filename);
+
+    if fs::exists(&final_out)? {
+        return Ok(());
+    }
+
+    if !out_dir.exists() {
+        fs::create_dir_all(&out_dir)?;
+    }
+
+    let keep_in = options
+        .keep_staging
+        .then(|| kept_staging_dir(&dl_dir, info));
+    // With a `temp_path`, yt-dlp stages the download and moves it into `out_dir` itself,
+    // so `dl_dir` only holds the logs and the info JSON.
+    let native = yt_dlp.temp_path.is_some();
+    if !native && tmp_out_path.exists() {
+        return finish_dl(&dl_dir, &tmp_out_path, &final_out, keep_in.as_deref());
+    }
+    let mut yt_dlp = yt_dlp.clone();
+    let output = if native {
+        yt_dlp.home_path(Some(&out_dir));
+        &output_filename
+    } else {
+        &tmp_out_path
+    };
+
+    // Logs in `dl_dir` are removed along with it once the download is done.
+    let (stdout_path, stderr_path) = match &options.log_dir {
+        Some(log_dir) => {
+            fs::create_dir_all(log_dir)?;
+            let name = dl_dir
+                .file_name()
+                .map(|x| x.to_string_lossy().replace(':', "-"))
+                .unwrap_or_else(|| info.id.clone());
+            (
+                log_dir.join(format!("{name}.stdout.log")),
+                log_dir.join(format!("{name}.stderr.log")),
+            )
+        }
+        None => (
+            dl_dir.join("yt-dlp-stdout.log"),
+            dl_dir.join("yt-dlp-stderr.log"),
+        ),
+    };
+    let filepath_path = dl_dir.join("yt-dlp-filepath.txt");
+
+    // A partial download left by an earlier attempt, e.g. before a restart, is resumed
+    // rather than started over, which matters for long streams.
+    let resume = has_partial(&dl_dir);
+    if resume {
+        log!("Resuming the partial download of {url}");
+        let _ = fs::remove_file(&filepath_path);
+    } else if fs::exists(&dl_dir).is_ok_and(|x| x == true) {
+        fs::remove_dir_all(&dl_dir)?;
+    }
+    fs::create_dir_all(&dl_dir)?;
+
+    // Logs in `log_dir` are appended to by every attempt, so only look at this one's.
+    let log_start = |path: &Path| fs::metadata(path).map_or(0, |x| x.len() as usize);
+    let log_starts = [log_start(&stdout_path), log_start(&stderr_path)];
+    let mut oo = OpenOptions::new();
+    oo.create(true).append(true);
+    let stdout_log = oo.open(&stdout_path).ok();
+    let stderr = match oo.open(&stderr_path) {
+        Ok(x) => Stdio::from(x),
+        Err(_) => Stdio::null(),
+    };
+
+    let mut child = yt_dlp
+        .command_with_args()
+        .current_dir(&dl_dir)
+        // yt-dlp often doesn't write to what it says it will, so that's why I must
+        // remind it to.
+        .arg(url)
+        .arg("--output")
+        .arg(output)
+        .arg("--write-info-json")
+        .arg("--paths")
+        .arg(format!("infojson:{}", dl_dir.to_string_lossy()))
+        .args(["--print-to-file", "after_move:filepath"])
+        .arg(&filepath_path)
+        .arg("--newline")
+        .arg("--progress-template")
+        .arg(DlProgress::template())
+        .args(yt_dlp.archive_args())
+        .args(match resume {
+            true => &["--continue", "--no-overwrites"][..],
+            false => &[],
+        })
+        .stdout(Stdio::piped())
+        .stderr(stderr)
+        .spawn()?;
+    status.pid.store(child.id(), Ordering::Relaxed);
+    status.started.store(true, Ordering::Relaxed);
+    let chat = options
+        .download_chat
+        .then(|| spawn_chat(&yt_dlp, url, &dl_dir, output))
+        .flatten();
+    if let Some(chat) = &chat {
+        status.add_child(chat.id());
+    }
+    let exit = std::thread::scope(|s| {
+        if let Some(output) = child.stdout.take() {
+            s.spawn(|| log_progress(output, stdout_log, &status.reported));
+        }
+        child.wait()
+    });
+    status.pid.store(0, Ordering::Relaxed);
+    if let Some(chat) = chat {
+        let pid = chat.id();
+        finish_chat(chat, exit.as_ref().is_ok_and(|x| x.success()), url);
+        status.remove_child(pid);
+    }
+    let exit = exit?;
+    // yt-dlp doesn't always fail when it aborts a download for being too large. It
+    // only does that when it knows the size up front; otherwise, we stop it.
+    let hit_size_limit =
+        [&stdout_path, &stderr_path]
+            .iter()
+            .zip(log_starts)
+            .any(|(path, start)| {
+                fs::read_to_string(path).is_ok_and(|log| {
+                    log.get(start..)
+                        .is_some_and(|x| x.contains("larger than max-filesize"))
+                })
+            });
+    let size_limited = hit_size_limit || status.size_limited.load(Ordering::Relaxed);
+    let size_limit = |kept| error::Error::SizeLimit {
+        limit: yt_dlp
+            .max_filesize
+            .clone()
+            .unwrap_or_else(|| "?".to_string()),
+        kept,
+    };
+    if let Some(signal) = exit.signal()
+        && !size_limited
+    {
+        // We're probably shutting down; leave everything as-is so it can be resumed.
+        return Err(error::Error::Killed { signal });
+    }
+    if !exit.success() && !size_limited {
+        let stderr = log_tail(&stderr_path, STDERR_TAIL_LINES);
+        // yt-dlp fails the whole download when subtitles it found can't be downloaded,
+        // but they aren't worth losing the video over.
+        if yt_dlp.sub_langs.is_some()
+            && stderr.contains("Unable to download video subtitles")
+        {
+            log!(
+                warn,
+                "Failed to download subtitles for {url}, trying again without them"
+            );
+            yt_dlp.sub_langs(None);
+            return dl(&yt_dlp, options, info, url, dl_dir, status);
+        }
+        options.note_rate_limit(&yt_dlp, &stderr);
+        return Err(error::Error::YtDlpFailure {
+            code: exit.code().unwrap_or(-1),
+            stderr,
+        });
+    }
+
+    // yt-dlp succeeds without downloading anything for videos in the archive.
+    let archived = fs::read_to_string(&stdout_path).is_ok_and(|log| {
+        log.get(log_starts[0]..)
+            .is_some_and(|x| x.contains("has already been recorded in the archive"))
+    });
+    if archived {
+        fs::remove_dir_all(&dl_dir)?;
+        return Err(error::Error::Skipped(
+            "it's already in the download archive".to_string(),
+        ));
+    }
+
+    let info_json = read_info_json(&dl_dir);
+    // Read now, since it's deleted along with `dl_dir` once the download is moved.
+    let raw_info_json = options
+        .write_info_json
+        .then(|| info_json_path(&dl_dir))
+        .flatten()
+        .and_then(|x| fs::read(x).ok());
+    let written = fs::read_to_string(&filepath_path)
+        .ok()
+        .map(|path| PathBuf::from(path.trim()))
+        .filter(|path| !path.as_os_str().is_empty());
+    // Without remuxing, the predicted extension is wrong if yt-dlp merged the formats
+    // into a different container, so use the path it actually wrote to if we can.
+    let final_out = match written {
+        // Keep what it got before it was stopped.
+        _ if size_limited => {
+            let staged = match &yt_dlp.temp_path {
+                Some(temp_path) if native => temp_path.join(&output_filename),
+                _ => tmp_out_path,
+            };
+            let Some((partial, extension)) = stopped_download(&staged) else {
+                remove_staging(&dl_dir, keep_in.as_deref())?;
+                return Err(size_limit(None));
+            };
+            let final_out = final_out.with_extension(extension);
+            finish_dl(&dl_dir, &partial, &final_out, keep_in.as_deref())?;
+            final_out
+        }
+        // yt-dlp has already moved it into place.
+        Some(path) if native => {
+            remove_staging(&dl_dir, keep_in.as_deref())?;
+            path
+        }
+        None if native => {
+            remove_staging(&dl_dir, keep_in.as_deref())?;
+            final_out
+        }
+        Some(path) => {
+            let final_out = match path.file_name() {
+                Some(name) => out_dir.join(name),
+                None => final_out,
+            };
+            finish_dl(&dl_dir, &path, &final_out, keep_in.as_deref())?;
+            final_out
+        }
+        None => {
+            finish_dl(&dl_dir, &tmp_out_path, &final_out, keep_in.as_deref())?;
+            final_out
+        }
+    };
+
+    if yt_dlp.sub_langs.is_some() {
+        let mut langs = info_json
+            .iter()
+            .flat_map(|x| x.requested_subtitles.iter().flatten())
+            .map(|(lang, _)| lang.as_str())
+            .collect::<Vec<_>>();
+        langs.sort_unstable();
+        log!(
+            "Subtitles for {:?}: {}",
+            info.title,
+            if langs.is_empty() {
+                "none".to_string()
+            } else {
+                langs.join(", ")
+            }
+        );
+    }
+
+    if options.write_nfo
+        && let Some(info_json) = &info_json
+    {
+        let nfo_path = final_out.with_extension("nfo");
+        fs::write(&nfo_path, info_json.to_nfo()).map_err(|source| {
+            error::Error::File {
+                path: nfo_path.clone(),
+                source,
+            }
+        })?;
+    }
+    if let Some(json) = raw_info_json {
+        let json_path = final_out.with_extension("info.json");
+        fs::write(&json_path, json).map_err(|source| error::Error::File {
+            path: json_path.clone(),
+            source,
+        })?;
+    }
+    if size_limited {
+        // It's cut off, so it won't pass verification or the bounds checks.
+        return Err(size_limit(Some(final_out)));
+    }
+    if options.verify {
+        verify(&final_out)?;
+    }
+
+    // The duration of live streams isn't known until they end, and their formats
+    // sometimes aren't known when they start, so yt-dlp's match filter lets them
+    // through. Check them again now that they are. It's already downloaded by now, so
+    // it's kept either way.
+    let duration = info_json
+        .as_ref()
+        .and_then(|x| x.duration)
+        .or_else(|| probe_duration(&final_out));
+    let skip = if let Some(duration) = duration
+        && !yt_dlp.duration_in_bounds(duration)
+    {
+        Some(format!(
+            "duration of {duration:.0}s is outside of the configured bounds"
+        ))
+    } else if let Some(info_json) = &info_json
+        && !yt_dlp.height_allowed(info_json.height)
+    {
+        Some(format!(
+            "below the minimum resolution of {}p",
+            yt_dlp.min_height.unwrap_or_default()
+        ))
+    } else {
+        None
+    };
+    if let Some(reason) = skip {
+        return Err(error::Error::Skipped(format!(
+            "{reason}, but it was kept at {final_out:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// The duration of the media at `path` in seconds, according to ffprobe.
+fn probe_duration(path: &Path) -> Option<f64> {
+    let output = std::process::Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Read `path` all the way through with ffprobe, failing if it finds any errors.
+fn verify(path: &Path) -> error::Result<()> {
+    let output = std::process::Command::new("ffprobe")
+        .args(["-v", "error", "-count_packets"])
+        .args(["-show_entries", "stream=nb_read_packets", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .map_err(|e| error::Error::Unverified(format!("couldn't run ffprobe: {e}")))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        return Err(error::Error::Unverified(stderr.trim().to_string()));
+    }
+    if !output.status.success() {
+        return Err(error::Error::Unverified(format!(
+            "ffprobe exited with {}",
+            output.status
+        )));
+    }
+    Ok(())
+}
+
+/// The biggest file yt-dlp was downloading to for `output` when it was stopped, e.g.
+/// `video.f299.mp4.part`, and the extension it would have had.
+fn stopped_download(output: &Path) -> Option<(PathBuf, String)> {
+    const MEDIA: &[&str] = &["mp4", "mkv", "webm", "ts", "flv", "mov", "m4a", "opus"];
+    let stem = output.file_stem()?.to_str()?;
+    fs::read_dir(output.parent()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let rest = name.strip_prefix(stem)?.strip_prefix('.')?;
+            let rest = rest.strip_suffix(".part").unwrap_or(rest);
+            let extension = rest.rsplit('.').next()?;
+            if !MEDIA.contains(&extension) {
+                return None;
+            }
+            let len = entry.metadata().ok()?.len();
+            Some((len, entry.path(), extension.to_string()))
+        })
+        .max_by_key(|(len, ..)| *len)
+        .map(|(_, path, extension)| (path, extension))
+}
+
+/// Copy yt-dlp's `output` to `log`, except for progress lines, which are parsed into
+/// `reported` instead.
+fn log_progress(
+    output: impl Read,
+    mut log: Option<File>,
+    reported: &Mutex<Option<DlProgress>>,
+) {
+    for line in BufReader::new(output).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match DlProgress::parse(&line) {
+            Some(progress) => *reported.lock().unwrap() = Some(progress),
+            None => {
+                if let Some(log) = &mut log {
+                    let _ = writeln!(log, "{line}");
+                }
+            }
+        }
+    }
+}
+
+/// Marks the line with the file name we ask yt-dlp to print.
+const FILENAME_PREFIX: &str = "[vdl-filename]";
+
+/// Marks the progress lines we ask yt-dlp to print.
+const PROGRESS_PREFIX: &str = "[vdl-progress]";
+
+/// Download progress, as reported by yt-dlp.
+#[derive(Debug, Clone, Default, Serialize)]
+struct DlProgress {
+    downloaded_bytes: Option<u64>,
+    /// Bytes per second.
+    speed:            Option<f64>,
+    /// Seconds until it's done. Live streams don't have one.
+    eta:              Option<u64>,
+    /// Seconds since it started.
+    elapsed:          Option<f64>,
+}
+
+impl DlProgress {
+    /// The `--progress-template` for the lines `parse` understands.
+    fn template() -> String {
+        format!(
+            "download:{PROGRESS_PREFIX} %(progress.downloaded_bytes)s %(progress.speed)s \
+             %(progress.eta)s %(progress.elapsed)s"
+        )
+    }
+
+    /// Parse a line printed with `template`. Unknown values are printed as "NA".
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.strip_prefix(PROGRESS_PREFIX)?.split_whitespace();
+        let mut next = || fields.next().and_then(|x| x.parse::<f64>().ok());
+        Some(Self {
+            downloaded_bytes: next().map(|x| x as u64),
+            speed:            next(),
+            eta:              next().map(|x| x as u64),
+            elapsed:          next(),
+        })
+    }
+}
+
+impl std::fmt::Display for DlProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.speed, self.eta) {
+            (Some(speed), Some(eta)) => {
+                write!(f, "{}/s, ETA {}", format_bytes(speed), format_duration(eta))
             }
+            _ => write!(
+                f,
+                "{} in {}",
+                format_bytes(self.downloaded_bytes.unwrap_or(0) as f64),
+                format_duration(self.elapsed.unwrap_or(0.0) as u64)
+            ),
         }
     }
 }
 
-#[derive(Debug, Parser)]
-#[command(version, about)]
-enum Args {
-    /// Spawn the VOD downloading server.
-    Watch {
-        #[arg(short, long)]
-        silent: bool,
-    },
+/// Format a number of bytes like `12.3MiB`.
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1}{}", UNITS[unit])
+}
 
-    /// Communicate with the locally running VDL server.
-    Ipc {
-        #[command(subcommand)]
-        subcommand: IpcCommand,
-    },
-    /// Write shell-completions and exit.
-    Completions,
+/// Format a number of seconds like `01:02` or `1:02:03`.
+fn format_duration(secs: u64) -> String {
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{mins:02}:{secs:02}")
+    } else {
+        format!("{mins:02}:{secs:02}")
+    }
 }
 
-#[derive(Debug, Subcommand)]
-enum IpcCommand {
-    /// Find out what streams the server is currently downloading, if any.
-    GetWatching,
-    /// Find out what streams the server has downloaded.
-    GetDownloaded,
+/// The total size of the files under `dir`.
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            Some(if metadata.is_dir() {
+                dir_size(&entry.path())
+            } else {
+                metadata.len()
+            })
+        })
+        .sum()
 }
 
-fn main() -> eyre::Result<()> {
-    let command = Args::parse();
-    match command {
-        Args::Watch { silent } => serve(silent),
-        Args::Ipc { subcommand } => ipc(subcommand),
-        Args::Completions => {
-            use clap::CommandFactory;
-            use clap_complete::Shell;
-            let mut cmd = Args::command();
-            let shell = Shell::from_env()
-                .ok_or_else(|| eyre!("Couldn't determine shell from environment!"))?;
+/// The current time as a Unix timestamp.
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs() as i64)
+}
 
-            match shell {
-                Shell::Fish => {
-                    let vendor_completions_dir = dirs::data_dir()
-                        .expect("data dir")
-                        .join("fish/vendor_completions.d");
-                    let vendor_completions_path = vendor_completions_dir.join("vdl.fish");
-                    if !vendor_completions_dir.exists() {
-                        fs::create_dir_all(&vendor_completions_dir)?;
-                    }
-                    let mut f = File::create(&vendor_completions_path)?;
-                    eprintln!("Writing completions to {vendor_completions_path:?}");
-                    clap_complete::generate(shell, &mut cmd, NAME, &mut f);
-                }
-                _ => {
-                    clap_complete::generate(
-                        shell,
-                        &mut cmd,
-                        NAME,
-                        &mut std::io::stdout(),
-                    );
+/// How long to let the chat download carry on after the video is done.
+const CHAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Start downloading the live chat for `url` to `output`'s name. yt-dlp downloads
+/// subtitles before the video, and the live chat of a live stream only ends with
+/// the stream, so this is a separate process to keep it from holding up the video.
+fn spawn_chat(
+    yt_dlp: &YtDlp,
+    url: &str,
+    dl_dir: &Path,
+    output: &Path,
+) -> Option<std::process::Child> {
+    let log = File::create(dl_dir.join("yt-dlp-chat.log")).ok();
+    let child = yt_dlp
+        .command_with_args()
+        .current_dir(dl_dir)
+        .arg(url)
+        .arg("--output")
+        .arg(output)
+        .args([
+            "--skip-download",
+            "--write-subs",
+            "--sub-langs",
+            "live_chat",
+        ])
+        .stdout(Stdio::null())
+        .stderr(log.map_or_else(Stdio::null, Stdio::from))
+        .spawn();
+    match child {
+        Ok(child) => Some(child),
+        Err(e) => {
+            log!(warn, "Failed to start downloading the chat for {url}: {e}");
+            None
+        }
+    }
+}
+
+/// Wait for the chat download to finish if the video was downloaded, or stop it if
+/// it wasn't. Not getting the chat doesn't fail the download.
+fn finish_chat(mut chat: std::process::Child, wait: bool, url: &str) {
+    let started = Instant::now();
+    while wait && started.elapsed() < CHAT_TIMEOUT {
+        match chat.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    log!(warn, "Failed to download the chat for {url}");
                 }
-            };
-            Ok(())
+                return;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(500)),
+            Err(_) => break,
         }
     }
+    if wait {
+        log!(warn, "Gave up on downloading the chat for {url}");
+    }
+    let _ = chat.kill();
+    let _ = chat.wait();
 }
 
-fn ipc(command: IpcCommand) -> eyre::Result<()> {
-    let runtime_dir = dirs::runtime_dir().expect("User runtime dir").join(NAME);
-    let socket = runtime_dir.join("ipc.sock");
-    let mut stream = UnixStream::connect(&socket).map_err(|e| {
-        eyre!(
-            "Couldn't connect to socket {socket:?} (ensure an instance is running): {e}"
-        )
-    })?;
+/// How many lines of yt-dlp's stderr to include in download errors.
+const STDERR_TAIL_LINES: usize = 5;
 
-    let request = match command {
-        IpcCommand::GetWatching => IpcRequest::GetWatching,
-        IpcCommand::GetDownloaded => IpcRequest::GetDownloaded,
+/// The last `n` non-empty lines of the log at `path`.
+fn log_tail(path: &Path, n: usize) -> String {
+    let Ok(log) = fs::read_to_string(path) else {
+        return String::new();
     };
-    let request_json = serde_json::ser::to_vec(&request)?;
-    stream.write(&request_json)?;
-    stream.shutdown(Shutdown::Write)?;
+    let lines: Vec<&str> = log.lines().filter(|x| !x.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(n)..].join("\n")
+}
 
-    let mut response_json = Vec::new();
-    stream.read_to_end(&mut response_json)?;
+/// The parts of the info json written by `--write-info-json` that we care about.
+#[derive(Debug, Deserialize)]
+struct InfoJson {
+    title:               Option<String>,
+    uploader:            Option<String>,
+    description:         Option<String>,
+    /// `YYYYMMDD`
+    upload_date:         Option<String>,
+    duration:            Option<f64>,
+    /// The height of the video format, which is missing for audio-only ones.
+    height:              Option<u32>,
+    /// The subtitles being downloaded, by language.
+    requested_subtitles: Option<HashMap<String, serde::de::IgnoredAny>>,
+}
 
-    let response: IpcResponse = serde_json::de::from_slice(&response_json)?;
-    match response {
-        IpcResponse::Watching(watching) => {
-            // TODO: Tabular display
-            println!("Watching {} streams", watching.len());
-            if !watching.is_empty() {
-                for info in watching {
-                    println!(
-                        ":: {:?} - {} ({:?})",
-                        info.title, info.uploader, info.webpage_url
-                    );
-                }
-            }
+impl InfoJson {
+    /// Build a Kodi-style `.nfo` file.
+    fn to_nfo(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace('\'', "&apos;")
         }
-        IpcResponse::Downloaded(downloaded) => {
-            // TODO: Tabular display
-            println!("Downloaded {} streams", downloaded.len());
-            if !downloaded.is_empty() {
-                for info in downloaded {
-                    println!(
-                        ":: {:?} - {} ({:?})",
-                        info.title, info.uploader, info.webpage_url,
-                    );
-                }
-            }
+
+        let mut nfo = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<movie>\n",
+        );
+        if let Some(title) = &self.title {
+            nfo += &format!("  <title>{}</title>\n", escape(title));
         }
-        IpcResponse::Error(e) => {
-            eprintln!("{e}");
-            std::process::exit(1);
+        if let Some(uploader) = &self.uploader {
+            nfo += &format!("  <studio>{}</studio>\n", escape(uploader));
+        }
+        if let Some(date) = self.upload_date.as_deref().filter(|x| x.len() == 8) {
+            let (year, rest) = date.split_at(4);
+            let (month, day) = rest.split_at(2);
+            nfo += &format!("  <premiered>{year}-{month}-{day}</premiered>\n");
+        }
+        if let Some(description) = &self.description {
+            nfo += &format!("  <plot>{}</plot>\n", escape(description));
         }
+        nfo += "</movie>\n";
+        nfo
     }
-    Ok(())
 }
 
-fn serve(silent: bool) -> eyre::Result<()> {
-    let reload_config = Arc::new(AtomicBool::new(false));
-    let exit = Arc::new(AtomicBool::new(false));
-    signal_hook::flag::register(SIGHUP, reload_config.clone()).unwrap();
-    signal_hook::flag::register(SIGTERM, exit.clone()).unwrap();
+/// Where yt-dlp wrote the info json in `dl_dir`, if it did.
+fn info_json_path(dl_dir: &Path) -> Option<PathBuf> {
+    let entry = fs::read_dir(dl_dir)
+        .ok()?
+        .flatten()
+        .find(|x| x.file_name().to_string_lossy().ends_with(".info.json"))?;
+    Some(entry.path())
+}
 
-    let config_path = dirs::config_dir()
-        .expect("config dir")
-        .join(NAME)
-        .join("config.toml");
-    let mut config = Config::load(&config_path)?;
+/// Read the info json yt-dlp wrote to `dl_dir`, if there is one.
+fn read_info_json(dl_dir: &Path) -> Option<InfoJson> {
+    let json = fs::read_to_string(info_json_path(dl_dir)?).ok()?;
+    serde_json::from_str(&json).ok()
+}
 
-    if let Some(dir) = config.dir.clone().or_else(dirs::video_dir) {
-        std::env::set_current_dir(&dir).map_err(|e| eyre!("{dir:?}: {e}"))?;
-    }
+/// Move the download at `tmp_out_path` to `final_out`, then remove `dl_dir`. Any other
+/// files yt-dlp left next to the download (e.g. the original kept by `--keep-video`)
+/// are moved alongside it. With `keep_in`, they're copied instead, and `dl_dir` is
+/// moved there.
+fn finish_dl(
+    dl_dir: &Path,
+    tmp_out_path: &Path,
+    final_out: &Path,
+    keep_in: Option<&Path>,
+) -> error::Result<()> {
+    let transfer: fn(&Path, &Path) -> std::io::Result<()> = match keep_in {
+        Some(_) => |from, to| fs::copy(from, to).map(drop),
+        None => |from, to| fs::rename(from, to),
+    };
+    transfer(tmp_out_path, final_out).map_err(|source| error::Error::Move {
+        from: tmp_out_path.to_path_buf(),
+        to: final_out.to_path_buf(),
+        source,
+    })?;
 
-    let subscriber = Subscriber::default();
-    let inner = subscriber.inner.clone();
-    {
-        let mut inner = inner.lock().unwrap();
-        inner.ids = config.ids;
+    // Move the sidecar files (thumbnails, descriptions, subtitles, ...) along with it.
+    if let Some(stem) = tmp_out_path.file_stem().and_then(|x| x.to_str()) {
+        for entry in fs::read_dir(dl_dir)? {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if !name.starts_with(stem)
+                || name.ends_with(".info.json")
+                || name.ends_with(".part")
+                || name.contains(".part-Frag")
+                || name.ends_with(".ytdl")
+            {
+                continue;
+            }
+            let from = entry.path();
+            let to = final_out.with_file_name(name);
+            transfer(&from, &to).map_err(|source| error::Error::Move {
+                from,
+                to,
+                source,
+            })?;
+        }
     }
 
-    let ipc = Ipc::new(inner.clone())?;
-    std::thread::spawn(move || ipc.spawn());
-
-    YtDlp::download_latest()?;
-
-    let subscriber = std::thread::spawn(move || subscriber.spawn(silent));
+    remove_staging(dl_dir, keep_in)
+}
 
-    loop {
-        if subscriber.is_finished() {
-            eprintln!("Subscriber exited!");
-            subscriber.join().unwrap()?;
-            std::process::exit(1);
-        }
-        if exit.swap(false, Ordering::Relaxed) {
-            let runtime_dir = dirs::runtime_dir().expect("User runtime dir").join(NAME);
-            let socket = runtime_dir.join("ipc.sock");
-            let _ = fs::remove_file(socket);
-            std::process::exit(1);
-        }
-        if reload_config.swap(false, Ordering::Relaxed) {
-            eprintln!("Reloading config..");
-            match Config::load(&config_path) {
-                Ok(c) => {
-                    eprintln!("Reloaded config!");
-                    let old_dir = config.dir;
-                    config = c;
-                    let mut inner = inner.lock().unwrap();
-                    inner.ids = config.ids;
-                    if config.dir.is_some() && config.dir != old_dir {
-                        let dir = config.dir.as_deref().unwrap();
-                        std::env::set_current_dir(dir)
-                            .map_err(|e| eyre!("{dir:?}: {e}"))?;
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Failed to reload config (retaining previous config): {e}")
-                }
+/// Remove `dl_dir`, or move it to `keep_in` to keep it.
+fn remove_staging(dl_dir: &Path, keep_in: Option<&Path>) -> error::Result<()> {
+    let Some(keep_in) = keep_in else {
+        fs::remove_dir_all(dl_dir)?;
+        return Ok(());
+    };
+    if let Some(parent) = keep_in.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // The cache and the state directory can be on different filesystems.
+    fs::rename(dl_dir, keep_in)
+        .or_else(|e| match e.kind() {
+            std::io::ErrorKind::CrossesDevices => {
+                copy_dir(dl_dir, keep_in).and_then(|()| fs::remove_dir_all(dl_dir))
             }
-        }
+            _ => Err(e),
+        })
+        .map_err(|source| error::Error::Move {
+            from: dl_dir.to_path_buf(),
+            to: keep_in.to_path_buf(),
+            source,
+        })
+}
 
-        std::thread::sleep(std::time::Duration::from_millis(500));
+/// Copy the directory `from` and everything in it to `to`.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let to = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else {
+            fs::copy(entry.path(), to)?;
+        }
     }
+    Ok(())
 }
 
-fn live_info(yt_dlp: &YtDlp, id: &str) -> eyre::Result<Option<YtLiveInfo>> {
-    let url = format!("https://www.youtube.com/{id}/live");
-    let output = yt_dlp.command_with_args().arg("-J").arg(url).output()?;
-    let stdout = String::from_utf8(output.stdout)?;
-    let stdout = stdout.trim();
-    if stdout.is_empty() {
-        return Ok(None);
+/// Where `keep_staging` keeps the staging directory `dl_dir` of the download of
+/// `info`. Downloads of the same id are told apart by when they started.
+fn kept_staging_dir(dl_dir: &Path, info: &Info) -> PathBuf {
+    let name = dl_dir
+        .file_name()
+        .map(|x| x.to_string_lossy().replace(':', "-"))
+        .unwrap_or_else(|| info.id.clone());
+    paths::kept_dir().join(format!("{name}-{}", info.started_at.unwrap_or_default()))
+}
+
+/// `path` made absolute against the current directory, so it means the same thing
+/// wherever it's used from.
+fn absolute(path: PathBuf) -> PathBuf {
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// Replace characters that aren't allowed (or are troublesome) in file names.
+fn sanitize_filename(name: &str) -> String {
+    let name = name
+        .trim()
+        .replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|', '\0'], "_");
+    match name.as_str() {
+        "" | "." | ".." => "_".to_string(),
+        _ => name,
     }
-    let info: YtLiveInfo = serde_json::from_str(&stdout)?;
-    Ok(Some(info))
 }
 
-fn dl(yt_dlp: &YtDlp, url: &str, dl_dir: PathBuf) -> eyre::Result<()> {
-    let current_dir = std::env::current_dir()?;
-    let Ok(output) = yt_dlp
+/// Whether `dl_dir` has a partial download in it that yt-dlp can resume.
+fn has_partial(dl_dir: &Path) -> bool {
+    fs::read_dir(dl_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .any(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.ends_with(".part")
+                || name.contains(".part-Frag")
+                || name.ends_with(".ytdl")
+        })
+}
+
+/// Download `url` to the stdin of `command`, for `pipe_to`. There's no file, so none of
+/// the moving, checking, or sidecar files of `dl` apply.
+fn pipe_dl(
+    yt_dlp: &YtDlp,
+    url: &str,
+    command: &[String],
+    dl_dir: &Path,
+    status: &DlStatus,
+) -> error::Result<()> {
+    let [program, args @ ..] = command else {
+        return Err(error::Error::Parse("pipe_to is empty".to_string()));
+    };
+    fs::create_dir_all(dl_dir)?;
+    let stderr_path = dl_dir.join("yt-dlp-stderr.log");
+    let pipe_stderr_path = dl_dir.join("pipe-stderr.log");
+    let mut pipe = std::process::Command::new(program)
+        .args(args)
+        .current_dir(dl_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(File::create(&pipe_stderr_path)?)
+        .spawn()?;
+    status.add_child(pipe.id());
+    // yt-dlp can't remux or embed into stdout.
+    let mut yt_dlp = yt_dlp.clone();
+    yt_dlp.remux_video(None).sub_langs(None).temp_path(None);
+    let child = yt_dlp
         .command_with_args()
-        .args([&url, "--print", "_filename"])
-        .output()
-    else {
-        return Err(eyre!("Couldn't get filename"));
+        .current_dir(dl_dir)
+        .arg(url)
+        .args(["--output", "-"])
+        .args(yt_dlp.archive_args())
+        .stdout(pipe.stdin.take().expect("stdin is piped"))
+        .stderr(File::create(&stderr_path)?)
+        .spawn();
+    let mut child = match child {
+        Ok(x) => x,
+        Err(e) => {
+            let _ = pipe.kill();
+            let _ = pipe.wait();
+            status.remove_child(pipe.id());
+            return Err(e.into());
+        }
     };
-    let stdout = String::from_utf8(output.stdout)?;
-    let stdout = stdout.trim();
-    if stdout.is_empty() {
-        return Err(eyre!("Filename is empty"));
+    status.pid.store(child.id(), Ordering::Relaxed);
+    status.started.store(true, Ordering::Relaxed);
+    let exit = child.wait();
+    status.pid.store(0, Ordering::Relaxed);
+    // It sees the end of its input once yt-dlp exits.
+    let pipe_status = pipe.wait();
+    status.remove_child(pipe.id());
+    let pipe_status = pipe_status?;
+    let exit = exit?;
+    if let Some(signal) = exit.signal() {
+        return Err(error::Error::Killed { signal });
     }
-    let mut output_filename = PathBuf::from(stdout);
-    if let Some(format) = yt_dlp.remux_video.as_deref() {
-        output_filename.set_extension(format);
+    // yt-dlp fails too when what it's writing to goes away, but this is the cause.
+    if !pipe_status.success() {
+        return Err(error::Error::PipeFailure {
+            code:   pipe_status.code().unwrap_or(-1),
+            stderr: log_tail(&pipe_stderr_path, STDERR_TAIL_LINES),
+        });
     }
-    let final_out = current_dir.join(&output_filename);
-    let tmp_out_path = dl_dir.join(&output_filename);
-
-    if fs::exists(&final_out)? {
-        return Ok(());
+    if !exit.success() {
+        return Err(error::Error::YtDlpFailure {
+            code:   exit.code().unwrap_or(-1),
+            stderr: log_tail(&stderr_path, STDERR_TAIL_LINES),
+        });
     }
+    fs::remove_dir_all(dl_dir)?;
+    Ok(())
+}
 
-    if tmp_out_path.exists() {
-        fs::rename(&tmp_out_path, &final_out)
-            .map_err(|e| eyre!("{e}: {tmp_out_path:?} -> {final_out:?}"))?;
+fn yt_dl(
+    yt_dlp: &YtDlp,
+    options: &DlOptions,
+    info: &Info,
+    id: &str,
+    dl_dir: PathBuf,
+    status: &DlStatus,
+) -> eyre::Result<()> {
+    let url = format!("https://www.youtube.com/watch?v={id}");
+    Ok(dl(yt_dlp, options, info, &url, dl_dir, status)?)
+}
 
-        fs::remove_dir_all(dl_dir)?;
-        return Ok(());
+/// The ids of the YouTube videos in the playlist at `url`. Unless `ignore_errors` is
+/// set, any entry that can't be listed fails the whole listing. With
+/// `break_on_existing`, the listing stops at the first entry in the download archive.
+fn playlist_entries(
+    yt_dlp: &YtDlp,
+    url: &str,
+    ignore_errors: bool,
+    break_on_existing: bool,
+) -> error::Result<Vec<String>> {
+    #[derive(Deserialize)]
+    struct Playlist {
+        entries: Vec<Entry>,
     }
-
-    let stdout_path = dl_dir.join("yt-dlp-stdout.log");
-    let stderr_path = dl_dir.join("yt-dlp-stderr.log");
-
-    if fs::exists(&dl_dir).is_ok_and(|x| x == true) {
-        fs::remove_dir_all(&dl_dir)?;
+    #[derive(Deserialize)]
+    struct Entry {
+        id:     String,
+        ie_key: Option<String>,
     }
-    fs::create_dir_all(&dl_dir)?;
 
-    let mut oo = OpenOptions::new();
-    oo.create(true).append(true);
-    let stdout = match oo.open(&stdout_path) {
-        Ok(x) => Stdio::from(x),
-        Err(_) => Stdio::null(),
-    };
-    let stderr = match oo.open(&stderr_path) {
-        Ok(x) => Stdio::from(x),
-        Err(_) => Stdio::null(),
+    let on_error = if ignore_errors {
+        "--ignore-errors"
+    } else {
+        "--abort-on-error"
     };
-
-    let _status = yt_dlp
+    if break_on_existing {
+        // `-J` only prints once the whole playlist has been listed, which breaking
+        // stops, so print each entry as it's listed instead.
+        let output = yt_dlp
+            .command_with_args()
+            .args(["--flat-playlist", "--lazy-playlist", "--break-on-existing"])
+            .args(yt_dlp.archive_args())
+            .args(["--print", "%(ie_key)s %(id)s", url, on_error])
+            .output()?;
+        let stdout = String::from_utf8(output.stdout)?;
+        return Ok(stdout
+            .lines()
+            .filter_map(|line| line.strip_prefix("Youtube "))
+            .map(|x| x.trim().to_string())
+            .collect());
+    }
+    let output = yt_dlp
         .command_with_args()
-        .current_dir(&dl_dir)
-        // yt-dlp often doesn't write to what it says it will, so that's why I must
-        // remind it to.
-        .args([
-            &url,
-            "--output",
-            tmp_out_path.to_str().unwrap(),
-            "--write-info-json",
-        ])
-        .stdout(stdout)
-        .stderr(stderr)
-        .status()?;
-
-    fs::rename(&tmp_out_path, &final_out)
-        .map_err(|e| eyre!("{e}: {tmp_out_path:?} -> {final_out:?}"))?;
-    fs::remove_dir_all(dl_dir)?;
-    Ok(())
+        .args(["--flat-playlist", "-J", url, on_error])
+        .output()?;
+    let playlist: Playlist = serde_json::from_slice(&output.stdout)?;
+    Ok(playlist
+        .entries
+        .into_iter()
+        .filter(|x| x.ie_key.as_deref() == Some("Youtube"))
+        .map(|x| x.id)
+        .collect())
 }
 
-fn yt_dl(yt_dlp: &YtDlp, id: &str, dl_dir: PathBuf) -> eyre::Result<()> {
-    let url = format!("https://www.youtube.com/watch?v={id}");
-    dl(yt_dlp, &url, dl_dir)
+/// The videos recorded in yt-dlp's `--download-archive`.
+fn read_archive(path: Option<&Path>) -> HashSet<Id> {
+    let Some(archive) = path.and_then(|x| fs::read_to_string(x).ok()) else {
+        return HashSet::new();
+    };
+    archive
+        .lines()
+        .filter_map(|line| line.strip_prefix("youtube "))
+        .map(|yt_id| Id::Yt {
+            yt_id: yt_id.trim().to_string(),
+        })
+        .collect()
 }
 
 fn twitch_is_live(yt_dlp: &YtDlp, id: &str) -> bool {
@@ -623,37 +4165,170 @@ fn twitch_is_live(yt_dlp: &YtDlp, id: &str) -> bool {
         .is_ok_and(|x| !x.contains("The channel is not currently live"))
 }
 
-fn twitch_dl(yt_dlp: &YtDlp, id: &str, dl_dir: PathBuf) -> eyre::Result<()> {
+fn twitch_dl(
+    yt_dlp: &YtDlp,
+    options: &DlOptions,
+    info: &Info,
+    id: &str,
+    dl_dir: PathBuf,
+    status: &DlStatus,
+) -> eyre::Result<()> {
     let url = format!("https://www.twitch.tv/{id}");
-    dl(yt_dlp, &url, dl_dir)
+    Ok(dl(yt_dlp, options, info, &url, dl_dir, status)?)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct YtLiveInfo {
-    id:          String,
-    title:       String,
-    is_live:     bool,
-    was_live:    bool,
-    webpage_url: String,
-    uploader:    String,
+    id:                String,
+    title:             String,
+    is_live:           bool,
+    was_live:          bool,
+    webpage_url:       String,
+    uploader:          String,
+    duration:          Option<f64>,
+    /// Newer than `is_live` and `was_live`, and tells more states apart.
+    live_status:       Option<LiveStatus>,
+    /// When the stream is scheduled to start, as a Unix timestamp.
+    release_timestamp: Option<i64>,
+}
+
+impl YtLiveInfo {
+    fn status(&self) -> LiveStatus {
+        match self.live_status {
+            Some(status) => status,
+            None if self.is_live => LiveStatus::IsLive,
+            None if self.was_live => LiveStatus::WasLive,
+            None => LiveStatus::NotLive,
+        }
+    }
+
+    /// Whether this is a stream that's scheduled, but hasn't started yet.
+    fn is_upcoming(&self) -> bool {
+        self.status() == LiveStatus::IsUpcoming
+    }
+}
+
+/// yt-dlp's `live_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LiveStatus {
+    IsLive,
+    /// Scheduled, but not started yet.
+    IsUpcoming,
+    /// Ended, but the VOD is still being processed.
+    PostLive,
+    WasLive,
+    #[serde(other)]
+    NotLive,
+}
+
+impl From<YtLiveInfo> for Info {
+    fn from(info: YtLiveInfo) -> Self {
+        Self {
+            live_status:       Some(info.status()),
+            id:                info.id,
+            title:             info.title,
+            uploader:          info.uploader,
+            webpage_url:       info.webpage_url,
+            thumbnail:         None,
+            age_limit:         None,
+            release_timestamp: info.release_timestamp,
+            outcome:           None,
+            started_at:        None,
+            finished_at:       None,
+            subscription:      None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Info {
-    id:          String,
-    title:       String,
-    uploader:    String,
-    webpage_url: String,
+    id:                String,
+    title:             String,
+    uploader:          String,
+    webpage_url:       String,
+    thumbnail:         Option<String>,
+    /// The minimum age to watch this, if it's age restricted.
+    #[serde(default)]
+    age_limit:         Option<u32>,
+    /// When the stream is scheduled to start, as a Unix timestamp.
+    #[serde(default)]
+    release_timestamp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    live_status:       Option<LiveStatus>,
+    /// How the download ended, once it has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    outcome:           Option<Outcome>,
+    /// When the download started, as a Unix timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    started_at:        Option<i64>,
+    /// When the download finished, as a Unix timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    finished_at:       Option<i64>,
+    /// The subscription this was found through, e.g. a channel, if it isn't the
+    /// download itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subscription:      Option<Id>,
+}
+
+/// How a download ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Outcome {
+    Succeeded,
+    Failed {
+        error: String,
+    },
+    /// Downloaded, but `verify` found problems with the file.
+    Unverified {
+        error: String,
+    },
+    Cancelled,
+    /// Deliberately not downloaded, or not kept as a download, e.g. for being too
+    /// long.
+    Skipped {
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Succeeded => write!(f, "downloaded"),
+            // The error can include several lines of yt-dlp's output.
+            Outcome::Failed { error } => {
+                write!(f, "failed: {}", error.lines().next().unwrap_or_default())
+            }
+            Outcome::Unverified { error } => {
+                write!(f, "damaged: {}", error.lines().next().unwrap_or_default())
+            }
+            Outcome::Cancelled => write!(f, "cancelled"),
+            Outcome::Skipped { reason } => write!(f, "skipped: {reason}"),
+        }
+    }
 }
 
 impl Info {
-    fn get(yt_dlp: &YtDlp, id: &Id) -> eyre::Result<Self> {
+    /// How many seconds the download took, once it's finished.
+    fn elapsed(&self) -> Option<u64> {
+        Some(self.finished_at?.saturating_sub(self.started_at?).max(0) as u64)
+    }
+
+    /// Whether this is a live stream, or one that will be once it starts.
+    fn is_live(&self) -> bool {
+        matches!(
+            self.live_status,
+            Some(LiveStatus::IsLive | LiveStatus::IsUpcoming)
+        )
+    }
+
+    fn get(yt_dlp: &YtDlp, id: &Id) -> error::Result<Self> {
         let url = match id {
             Id::Yt { yt_id } => format!("https://www.youtube.com/watch?v={yt_id}"),
             Id::Twitch { twitch_id } => format!("https://www.twitch.tv/{twitch_id}"),
+            Id::Playlist { url } => url.clone(),
         };
-        let output = yt_dlp.command_with_args().args(["-J", &url]).output()?;
-        let stdout = String::from_utf8(output.stdout)?;
+        let stdout = fetch_json(yt_dlp, &url)?
+            .ok_or_else(|| error::Error::NotLive { url: url.clone() })?;
         Ok(serde_json::from_str(&stdout)?)
     }
 }
@@ -662,8 +4337,57 @@ impl Info {
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(untagged)]
 enum Id {
-    Yt { yt_id: String },
-    Twitch { twitch_id: String },
+    Yt {
+        yt_id: String,
+    },
+    Twitch {
+        twitch_id: String,
+    },
+    /// A YouTube playlist, or a channel's uploads. New entries are downloaded.
+    Playlist {
+        url: String,
+    },
+}
+
+impl Id {
+    /// Check that this could be a real id. YouTube channels can be `@handle` or
+    /// `channel/UC...`, which go in URLs, but nothing can climb out of them with `..`.
+    fn validate(&self) -> eyre::Result<()> {
+        let valid = match self {
+            Id::Yt { yt_id } => {
+                !yt_id.contains(['\\', '\0'])
+                    && yt_id
+                        .split('/')
+                        .all(|x| !x.is_empty() && x != "." && x != "..")
+            }
+            Id::Twitch { twitch_id } => {
+                !twitch_id.is_empty()
+                    && twitch_id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            // Playlists are never downloaded themselves, so their URL isn't used as a path.
+            Id::Playlist { url } => {
+                url.starts_with("https://") || url.starts_with("http://")
+            }
+        };
+        if !valid {
+            return Err(eyre!("{self} is not a valid id"));
+        }
+        Ok(())
+    }
+
+    /// Check that this can name a directory in the cache, as downloads do, without
+    /// escaping it. Only video ids are downloaded, and they never have a `/`.
+    fn validate_dir_name(&self) -> eyre::Result<()> {
+        self.validate()?;
+        match self {
+            Id::Yt { yt_id } if yt_id.contains('/') || yt_id.starts_with('.') => {
+                Err(eyre!("{self} can't be downloaded; it isn't a video id"))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl std::fmt::Display for Id {
@@ -671,6 +4395,7 @@ impl std::fmt::Display for Id {
         match self {
             Id::Yt { yt_id } => write!(f, "yt:{yt_id}"),
             Id::Twitch { twitch_id } => write!(f, "twitch:{twitch_id}"),
+            Id::Playlist { url } => write!(f, "playlist:{url}"),
         }
     }
 }
@@ -678,16 +4403,20 @@ impl std::fmt::Display for Id {
 impl std::str::FromStr for Id {
     type Err = eyre::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(s) = s.strip_prefix("yt:") {
-            Ok(Self::Yt {
+        let id = if let Some(s) = s.strip_prefix("yt:") {
+            Self::Yt {
                 yt_id: s.to_string(),
-            })
+            }
         } else if let Some(s) = s.strip_prefix("twitch:") {
-            Ok(Self::Twitch {
+            Self::Twitch {
                 twitch_id: s.to_string(),
-            })
+            }
+        } else if let Some(s) = s.strip_prefix("playlist:") {
+            Self::Playlist { url: s.to_string() }
         } else {
-            Err(eyre!("{s} is not a valid id"))
-        }
+            return Err(eyre!("{s} is not a valid id"));
+        };
+        id.validate()?;
+        Ok(id)
     }
 }
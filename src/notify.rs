@@ -0,0 +1,135 @@
+use crate::Info;
+use crate::log::log;
+use eyre::eyre;
+use std::time::Duration;
+
+pub const NTFY_SERVER: &str = "https://ntfy.sh";
+/// How many times to retry a Discord webhook after being rate limited.
+const DISCORD_RETRIES: usize = 3;
+
+/// Sends notifications about finished downloads.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    pub ntfy_topic:      Option<String>,
+    pub ntfy_server:     Option<String>,
+    pub discord_webhook: Option<String>,
+    pub connect_timeout: Option<Duration>,
+    /// How long to wait for each response, including its body.
+    pub read_timeout:    Option<Duration>,
+}
+
+impl Notifier {
+    /// Notify about a finished download. `error` is `None` if it succeeded.
+    ///
+    /// This doesn't block; notifications are sent from a separate thread and failures
+    /// are only logged.
+    pub fn download_finished(&self, info: &Info, error: Option<&str>) {
+        let notifier = self.clone();
+        let info = info.clone();
+        let error = error.map(str::to_string);
+        std::thread::spawn(move || {
+            let agent = notifier.agent();
+            if let Some(topic) = &notifier.ntfy_topic {
+                let server = notifier.ntfy_server.as_deref().unwrap_or(NTFY_SERVER);
+                if let Err(e) = ntfy(&agent, server, topic, &info, error.as_deref()) {
+                    log!(error, "Failed to send ntfy notification: {e}");
+                }
+            }
+            if let Some(webhook) = &notifier.discord_webhook
+                && let Err(e) = discord(&agent, webhook, &info, error.as_deref())
+            {
+                log!(error, "Failed to send Discord notification: {e}");
+            }
+        });
+    }
+
+    fn agent(&self) -> ureq::Agent {
+        ureq::Agent::config_builder()
+            .timeout_connect(self.connect_timeout)
+            .timeout_recv_response(self.read_timeout)
+            .timeout_recv_body(self.read_timeout)
+            .build()
+            .new_agent()
+    }
+}
+
+fn ntfy(
+    agent: &ureq::Agent,
+    server: &str,
+    topic: &str,
+    info: &Info,
+    error: Option<&str>,
+) -> eyre::Result<()> {
+    let url = format!("{}/{topic}", server.trim_end_matches('/'));
+    let (message, tag) = match error {
+        None => (
+            format!("Downloaded {:?} - {}", info.title, info.uploader),
+            "white_check_mark",
+        ),
+        Some(e) => (
+            format!(
+                "Failed to download {:?} - {}: {e}",
+                info.title, info.uploader
+            ),
+            "x",
+        ),
+    };
+    agent
+        .post(&url)
+        .header("Title", crate::NAME)
+        .header("Tags", tag)
+        .send(&message)?;
+    Ok(())
+}
+
+fn discord(
+    agent: &ureq::Agent,
+    webhook: &str,
+    info: &Info,
+    error: Option<&str>,
+) -> eyre::Result<()> {
+    const GREEN: u32 = 0x2ecc71;
+    const RED: u32 = 0xe74c3c;
+
+    let (description, color) = match error {
+        None => ("Downloaded".to_string(), GREEN),
+        Some(e) => (format!("Failed to download: {e}"), RED),
+    };
+    let mut embed = serde_json::json!({
+        "title": info.title,
+        "url": info.webpage_url,
+        "description": description,
+        "color": color,
+        "author": { "name": info.uploader },
+    });
+    if let Some(thumbnail) = &info.thumbnail {
+        embed["thumbnail"] = serde_json::json!({ "url": thumbnail });
+    }
+    let body = serde_json::to_string(&serde_json::json!({ "embeds": [embed] }))?;
+
+    for _ in 0..=DISCORD_RETRIES {
+        let mut response = agent
+            .post(webhook)
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .header("Content-Type", "application/json")
+            .send(&body)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        if status.as_u16() != 429 {
+            return Err(eyre!("Discord responded with {status}"));
+        }
+
+        #[derive(serde::Deserialize)]
+        struct RateLimited {
+            retry_after: f64,
+        }
+        let body = response.body_mut().read_to_string()?;
+        let RateLimited { retry_after } = serde_json::from_str(&body)?;
+        std::thread::sleep(Duration::from_secs_f64(retry_after.max(0.0)));
+    }
+    Err(eyre!("Discord kept rate limiting us"))
+}
@@ -0,0 +1,85 @@
+//! Where vdl keeps its files. Everything but the yt-dlp binary is namespaced by the
+//! `--instance`, if there is one.
+
+use crate::NAME;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static INSTANCE: OnceLock<String> = OnceLock::new();
+
+/// Namespace all paths by `name`. This must be called before any paths are used.
+pub fn set_instance(name: String) {
+    INSTANCE.set(name).expect("the instance is only set once");
+}
+
+pub fn config_file() -> PathBuf {
+    let config_dir = dirs::config_dir().expect("config dir").join(NAME);
+    match INSTANCE.get() {
+        Some(instance) => config_dir.join(format!("{instance}.toml")),
+        None => config_dir.join("config.toml"),
+    }
+}
+
+pub fn cache_dir() -> PathBuf {
+    let cache_dir = dirs::cache_dir().expect("cache dir");
+    match INSTANCE.get() {
+        Some(instance) => cache_dir.join(format!("{NAME}-{instance}")),
+        None => cache_dir.join(NAME),
+    }
+}
+
+/// Staging directories of finished downloads kept by `keep_staging`. This isn't in the
+/// cache, so `vdl prune` leaves them alone.
+pub fn kept_dir() -> PathBuf {
+    let state_dir = dirs::state_dir().expect("state dir").join(NAME);
+    match INSTANCE.get() {
+        Some(instance) => state_dir.join(format!("kept-{instance}")),
+        None => state_dir.join("kept"),
+    }
+}
+
+/// Downloads that were in progress when vdl last stopped.
+pub fn pending_file() -> PathBuf {
+    let state_dir = dirs::state_dir().expect("state dir").join(NAME);
+    match INSTANCE.get() {
+        Some(instance) => state_dir.join(format!("pending-{instance}.json")),
+        None => state_dir.join("pending.json"),
+    }
+}
+
+/// Subscriptions that were paused over IPC.
+pub fn paused_file() -> PathBuf {
+    let state_dir = dirs::state_dir().expect("state dir").join(NAME);
+    match INSTANCE.get() {
+        Some(instance) => state_dir.join(format!("paused-{instance}.json")),
+        None => state_dir.join("paused.json"),
+    }
+}
+
+/// yt-dlp's `--download-archive`, if the config doesn't set one.
+pub fn download_archive() -> PathBuf {
+    let state_dir = dirs::state_dir().expect("state dir").join(NAME);
+    match INSTANCE.get() {
+        Some(instance) => state_dir.join(format!("archive-{instance}.txt")),
+        None => state_dir.join("archive.txt"),
+    }
+}
+
+pub fn runtime_dir() -> PathBuf {
+    dirs::runtime_dir().expect("User runtime dir").join(NAME)
+}
+
+/// Locked by the running server, and holds its PID.
+pub fn lock_file() -> PathBuf {
+    match INSTANCE.get() {
+        Some(instance) => runtime_dir().join(format!("vdl-{instance}.pid")),
+        None => runtime_dir().join("vdl.pid"),
+    }
+}
+
+pub fn socket() -> PathBuf {
+    match INSTANCE.get() {
+        Some(instance) => runtime_dir().join(format!("ipc-{instance}.sock")),
+        None => runtime_dir().join("ipc.sock"),
+    }
+}
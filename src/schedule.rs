@@ -0,0 +1,69 @@
+//! Windows of local time in which an id is checked for live streams.
+
+use eyre::eyre;
+
+const DAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A point in the local week.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalTime {
+    /// 0 is Sunday.
+    pub day:    u32,
+    /// Minutes since midnight.
+    pub minute: u32,
+}
+
+impl LocalTime {
+    pub fn now() -> Self {
+        // SAFETY: `time` accepts a null pointer, and `localtime_r` only writes to `tm`.
+        let tm = unsafe {
+            let now = libc::time(std::ptr::null_mut());
+            let mut tm: libc::tm = std::mem::zeroed();
+            libc::localtime_r(&now, &mut tm);
+            tm
+        };
+        Self {
+            day:    tm.tm_wday as u32,
+            minute: (tm.tm_hour * 60 + tm.tm_min) as u32,
+        }
+    }
+}
+
+/// Whether `now` is within `hours`, e.g. `18:00-23:00`. Windows that end before they
+/// start go past midnight.
+pub fn in_hours(hours: &str, now: LocalTime) -> eyre::Result<bool> {
+    let (start, end) = hours
+        .split_once('-')
+        .ok_or_else(|| eyre!("{hours:?} isn't a range like \"18:00-23:00\""))?;
+    let (start, end) = (parse_time(start)?, parse_time(end)?);
+    Ok(if start <= end {
+        (start..end).contains(&now.minute)
+    } else {
+        now.minute >= start || now.minute < end
+    })
+}
+
+/// Whether `now` is on one of `days`, e.g. `["sat", "sun"]`.
+pub fn on_days(days: &[String], now: LocalTime) -> eyre::Result<bool> {
+    let mut on_day = false;
+    for day in days {
+        let i = DAYS
+            .iter()
+            .position(|x| day.to_lowercase().starts_with(x))
+            .ok_or_else(|| eyre!("{day:?} isn't a day of the week"))?;
+        on_day |= i as u32 == now.day;
+    }
+    Ok(on_day)
+}
+
+/// Parse `HH:MM` into minutes since midnight.
+fn parse_time(time: &str) -> eyre::Result<u32> {
+    let invalid = || eyre!("{time:?} isn't a time like \"18:00\"");
+    let (hour, minute) = time.trim().split_once(':').ok_or_else(invalid)?;
+    let hour: u32 = hour.parse().map_err(|_| invalid())?;
+    let minute: u32 = minute.parse().map_err(|_| invalid())?;
+    if hour > 24 || minute > 59 || (hour == 24 && minute != 0) {
+        return Err(invalid());
+    }
+    Ok(hour * 60 + minute)
+}
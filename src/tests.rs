@@ -0,0 +1,839 @@
+//! Tests for the download logic, with a shell script standing in for yt-dlp.
+
+use super::*;
+use std::os::unix::fs::PermissionsExt;
+
+/// Forking while another thread has a freshly written script open makes exec fail
+/// with ETXTBSY, so tests that run a mock yt-dlp take turns.
+static MOCK_LOCK: Mutex<()> = Mutex::new(());
+
+/// A fresh directory for `test` to work in.
+fn test_dir(test: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("vdl-test-{}-{test}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write a mock yt-dlp to `dir`. It prints `video.mkv` as the filename, after a
+/// warning like yt-dlp sometimes prints to stdout, and
+/// "downloads" by running `download` with `$out` set to the `--output` path,
+/// `$filepath` to the `--print-to-file` path, and `$args` to all the arguments.
+/// The arguments of every run are appended to `yt-dlp.args` in `dir`.
+fn mock_yt_dlp(dir: &Path, download: &str) -> YtDlp {
+    mock_yt_dlp_named(dir, "video.mkv", download)
+}
+
+/// `mock_yt_dlp`, printing `filename` as the filename instead. It's expanded by the
+/// shell, with `$args` set.
+fn mock_yt_dlp_named(dir: &Path, filename: &str, download: &str) -> YtDlp {
+    let script = format!(
+        r#"#!/bin/sh
+args="$*" out= filepath=
+echo "$args" >> "$0.args"
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --print) case "$2" in
+            "[vdl-filename]"*) echo 'WARNING: not the filename'; echo "[vdl-filename]{filename}"; exit 0 ;;
+        esac ;;
+        --output) out=$2; shift ;;
+        --print-to-file) filepath=$3; shift 2 ;;
+    esac
+    shift
+done
+{download}
+"#
+    );
+    let path = dir.join("yt-dlp");
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    let mut yt_dlp = YtDlp::default();
+    yt_dlp.with_exe(Some(&path));
+    yt_dlp
+}
+
+fn info() -> Info {
+    Info {
+        id:                "dQw4w9WgXcQ".to_string(),
+        title:             "A stream".to_string(),
+        uploader:          "Someone".to_string(),
+        webpage_url:       "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+        thumbnail:         None,
+        age_limit:         None,
+        release_timestamp: None,
+        live_status:       None,
+        outcome:           None,
+        started_at:        None,
+        finished_at:       None,
+        subscription:      None,
+    }
+}
+
+/// Run `dl` in `dir`, returning where it should put the download.
+fn run_dl(yt_dlp: &YtDlp, dir: &Path) -> (error::Result<()>, PathBuf) {
+    run_dl_with(yt_dlp, dir, DlOptions::default())
+}
+
+/// `run_dl` with `options`, other than the `out_dir`.
+fn run_dl_with(
+    yt_dlp: &YtDlp,
+    dir: &Path,
+    options: DlOptions,
+) -> (error::Result<()>, PathBuf) {
+    let options = DlOptions {
+        out_dir: dir.join("out"),
+        ..options
+    };
+    let result = dl(
+        yt_dlp,
+        &options,
+        &info(),
+        &info().webpage_url,
+        dir.join("cache"),
+        &DlStatus::default(),
+    );
+    (result, options.out_dir)
+}
+
+#[test]
+fn dl_moves_download_to_out_dir() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("moves");
+    let yt_dlp = mock_yt_dlp(&dir, r#"echo video > "$out"; echo "$out" > "$filepath""#);
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    assert_eq!(
+        fs::read_to_string(out_dir.join("video.mkv")).unwrap(),
+        "video\n"
+    );
+    assert!(!dir.join("cache").exists());
+}
+
+#[test]
+fn dl_moves_sidecar_files() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("sidecar");
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"echo video > "$out"; echo thumbnail > "${out%.mkv}.jpg"; echo "$out" > "$filepath""#,
+    );
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    assert!(out_dir.join("video.mkv").exists());
+    assert!(out_dir.join("video.jpg").exists());
+}
+
+#[test]
+fn dl_uses_the_path_yt_dlp_wrote_to() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("filepath");
+    // Like when the formats are merged into a different container than predicted.
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"out="${out%.mkv}.webm"; echo video > "$out"; echo "$out" > "$filepath""#,
+    );
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    assert!(out_dir.join("video.webm").exists());
+    assert!(!out_dir.join("video.mkv").exists());
+}
+
+#[test]
+fn dl_predicts_remuxed_filename() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("remux");
+    // Without `--print-to-file`, the predicted name is all there is to go on.
+    let mut yt_dlp = mock_yt_dlp(&dir, r#"echo video > "$out""#);
+    yt_dlp.remux_video(Some("mp4"));
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    assert!(out_dir.join("video.mp4").exists());
+}
+
+#[test]
+fn dl_reports_yt_dlp_failure() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("failure");
+    let yt_dlp = mock_yt_dlp(&dir, "echo 'ERROR: it broke' >&2; exit 3");
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    match result {
+        Err(error::Error::YtDlpFailure { code, stderr }) => {
+            assert_eq!(code, 3);
+            assert!(stderr.contains("it broke"), "{stderr:?}");
+        }
+        x => panic!("expected a yt-dlp failure, got {x:?}"),
+    }
+    assert!(!out_dir.join("video.mkv").exists());
+}
+
+#[test]
+fn dl_skips_existing_download() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("existing");
+    let yt_dlp = mock_yt_dlp(&dir, "exit 1");
+    fs::create_dir_all(dir.join("out")).unwrap();
+    fs::write(dir.join("out/video.mkv"), "already here").unwrap();
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    assert_eq!(
+        fs::read_to_string(out_dir.join("video.mkv")).unwrap(),
+        "already here"
+    );
+}
+
+#[test]
+fn dl_keeps_download_below_min_height() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("min-height");
+    // An audio-only stream, which has no height.
+    let mut yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"echo audio > "$out"; echo '{"height": null}' > "${out%.mkv}.info.json""#,
+    );
+    yt_dlp.min_height(Some(480));
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    assert!(
+        matches!(result, Err(error::Error::Skipped(_))),
+        "{result:?}"
+    );
+    // It's already been downloaded by the time its height is known.
+    assert!(out_dir.join("video.mkv").exists());
+    assert!(!dir.join("cache").exists());
+}
+
+#[test]
+fn parse_rate_units() {
+    assert_eq!(parse_rate("512").unwrap(), 512);
+    assert_eq!(parse_rate("50K").unwrap(), 50 * 1024);
+    assert_eq!(parse_rate("1.5m").unwrap(), 3 * 512 * 1024);
+    assert_eq!(parse_rate("2G").unwrap(), 2 << 30);
+    assert!(parse_rate("").is_err());
+    assert!(parse_rate("M").is_err());
+    assert!(parse_rate("-1M").is_err());
+    assert!(parse_rate("fast").is_err());
+    assert_eq!(parse_size("1T").unwrap(), 1 << 40);
+}
+
+#[test]
+fn dl_moves_chat_with_video() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("chat");
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"case "$args" in
+    *--skip-download*) echo chat > "${out%.mkv}.live_chat.json" ;;
+    *) echo video > "$out"; echo "$out" > "$filepath" ;;
+esac"#,
+    );
+    let options = DlOptions {
+        download_chat: true,
+        ..Default::default()
+    };
+
+    let (result, out_dir) = run_dl_with(&yt_dlp, &dir, options);
+    result.unwrap();
+    assert!(out_dir.join("video.mkv").exists());
+    assert!(out_dir.join("video.live_chat.json").exists());
+}
+
+#[test]
+fn dl_succeeds_without_chat() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("no-chat");
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"case "$args" in
+    *--skip-download*) exit 1 ;;
+    *) echo video > "$out"; echo "$out" > "$filepath" ;;
+esac"#,
+    );
+    let options = DlOptions {
+        download_chat: true,
+        ..Default::default()
+    };
+
+    let (result, out_dir) = run_dl_with(&yt_dlp, &dir, options);
+    result.unwrap();
+    assert!(out_dir.join("video.mkv").exists());
+}
+
+#[test]
+fn dl_keeps_logs_in_log_dir() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("log-dir");
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"echo 'a warning' >&2; echo video > "$out"; echo "$out" > "$filepath""#,
+    );
+    let options = DlOptions {
+        log_dir: Some(dir.join("logs")),
+        ..Default::default()
+    };
+
+    let (result, _) = run_dl_with(&yt_dlp, &dir, options);
+    result.unwrap();
+    let log = fs::read_to_string(dir.join("logs/cache.stderr.log")).unwrap();
+    assert!(log.contains("a warning"), "{log:?}");
+}
+
+#[test]
+fn dl_retries_without_failed_subtitles() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("subtitles");
+    let mut yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"case "$args" in
+    *--sub-langs*)
+        echo "ERROR: Unable to download video subtitles for 'en': HTTP Error 429" >&2
+        exit 1 ;;
+    *) echo video > "$out"; echo "$out" > "$filepath" ;;
+esac"#,
+    );
+    yt_dlp.sub_langs(Some("en.*"));
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    assert!(out_dir.join("video.mkv").exists());
+}
+
+#[test]
+fn cookie_files_skip_rate_limited() {
+    let [a, b, c] = ["a.txt", "b.txt", "c.txt"].map(PathBuf::from);
+    let mut files = CookieFiles::new(vec![a.clone(), b.clone(), c.clone()]);
+    assert_eq!(files.next(), Some(a.clone()));
+    assert_eq!(files.next(), Some(b.clone()));
+    files.rate_limited(&c);
+    assert_eq!(files.next(), Some(a.clone()));
+    assert_eq!(files.next(), Some(b.clone()));
+    files.rate_limited(&a);
+    files.rate_limited(&b);
+    // They're all rate limited, so the one that was the longest ago.
+    assert_eq!(files.next(), Some(c));
+    assert_eq!(CookieFiles::default().next(), None);
+}
+
+#[test]
+fn reload_keeps_cookie_files_rate_limited() {
+    let [a, b, c] = ["a.txt", "b.txt", "c.txt"].map(PathBuf::from);
+    let config = |files: &[&PathBuf]| Config {
+        cookies_files: files.iter().map(|x| absolute(x.to_path_buf())).collect(),
+        ..Default::default()
+    };
+    let mut inner = InnerSub::default();
+    inner.apply_config(&config(&[&a, &b]));
+    let cookie_files = inner.dl_options.cookie_files.clone();
+    cookie_files
+        .lock()
+        .unwrap()
+        .rate_limited(&absolute(a.clone()));
+
+    // The same files: nothing's lost, and downloads in progress share them still.
+    inner.apply_config(&config(&[&a, &b]));
+    assert!(Arc::ptr_eq(&inner.dl_options.cookie_files, &cookie_files));
+
+    // Different ones: `a` is still avoided.
+    inner.apply_config(&config(&[&a, &c]));
+    let mut files = inner.dl_options.cookie_files.lock().unwrap();
+    assert_eq!(files.next(), Some(absolute(c.clone())));
+    assert_eq!(files.next(), Some(absolute(c)));
+}
+
+#[test]
+fn dl_pipes_to_command() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("pipe");
+    let yt_dlp = mock_yt_dlp(&dir, r#"[ "$out" = - ] && echo video"#);
+    let piped = dir.join("piped");
+    let options = DlOptions {
+        pipe_to: Some(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!("cat > '{}'", piped.display()),
+        ]),
+        ..Default::default()
+    };
+
+    let (result, out_dir) = run_dl_with(&yt_dlp, &dir, options);
+    result.unwrap();
+    assert_eq!(fs::read_to_string(piped).unwrap(), "video\n");
+    assert!(!out_dir.exists());
+    assert!(!dir.join("cache").exists());
+}
+
+#[test]
+fn remux_target_rules() {
+    assert_eq!(remux_target("mkv", "webm"), Some("mkv"));
+    assert_eq!(remux_target("webm>mkv/mp4", "webm"), Some("mkv"));
+    assert_eq!(remux_target("webm>mkv/mp4", "MKV"), Some("mp4"));
+    assert_eq!(remux_target("webm>mkv", "mp4"), None);
+    assert!(check_remux_video("mov > mp4/mkv").is_ok());
+    assert!(check_remux_video("mov>/mkv").is_err());
+    assert!(check_remux_video("mp4//mkv").is_err());
+}
+
+#[test]
+fn dl_resumes_partial_download() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("resume");
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"case "$args" in
+    *"--continue --no-overwrites"*) cat "$out.part" > "$out"; rm "$out.part" ;;
+    *) echo video > "$out" ;;
+esac
+echo "$out" > "$filepath""#,
+    );
+    fs::create_dir_all(dir.join("cache")).unwrap();
+    fs::write(dir.join("cache/video.mkv.part"), "partial video\n").unwrap();
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    assert_eq!(
+        fs::read_to_string(out_dir.join("video.mkv")).unwrap(),
+        "partial video\n"
+    );
+    assert!(!dir.join("cache").exists());
+}
+
+#[test]
+fn dl_keeps_info_json() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("info-json");
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"echo video > "$out"; echo '{"filesize": 6}' > "${out%.mkv}.info.json"
+echo "$out" > "$filepath""#,
+    );
+    let options = DlOptions {
+        write_info_json: true,
+        ..Default::default()
+    };
+
+    let (result, out_dir) = run_dl_with(&yt_dlp, &dir, options);
+    result.unwrap();
+    assert_eq!(
+        fs::read_to_string(out_dir.join("video.info.json")).unwrap(),
+        "{\"filesize\": 6}\n"
+    );
+    assert!(!dir.join("cache").exists());
+}
+
+#[test]
+fn ids_cant_escape_the_cache() {
+    assert!("yt:../x".parse::<Id>().is_err());
+    assert!("yt:a/../b".parse::<Id>().is_err());
+    assert!("twitch:..".parse::<Id>().is_err());
+    assert!("twitch:a/b".parse::<Id>().is_err());
+    // Channels can have a `/`, but they're never downloaded themselves.
+    let channel = "yt:a/b".parse::<Id>().unwrap();
+    assert!(channel.validate_dir_name().is_err());
+    let handle = "yt:@handle".parse::<Id>().unwrap();
+    assert!(handle.validate().is_ok());
+    let video = "yt:dQw4w9WgXcQ".parse::<Id>().unwrap();
+    assert!(video.validate_dir_name().is_ok());
+}
+
+#[test]
+fn dl_skips_archived_video() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("archived");
+    let mut yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"case "$args" in
+    *--download-archive*) echo '[download] dQw4w9WgXcQ: has already been recorded in the archive' ;;
+    *) echo video > "$out"; echo "$out" > "$filepath" ;;
+esac"#,
+    );
+    yt_dlp.download_archive(Some(&dir.join("archive.txt")));
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    assert!(
+        matches!(result, Err(error::Error::Skipped(_))),
+        "{result:?}"
+    );
+    assert!(!out_dir.join("video.mkv").exists());
+    assert!(!dir.join("cache").exists());
+}
+
+#[test]
+fn dl_keeps_download_stopped_at_size_limit() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("size-limit");
+    // yt-dlp leaves the `.part` file and fails when it's interrupted.
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"echo video > "$(dirname "$out")/video.f299.mp4.part"; exit 1"#,
+    );
+    let out_dir = dir.join("out");
+    let options = DlOptions {
+        out_dir: out_dir.clone(),
+        ..Default::default()
+    };
+    let status = DlStatus::default();
+    status.size_limited.store(true, Ordering::Relaxed);
+
+    let result = dl(
+        &yt_dlp,
+        &options,
+        &info(),
+        &info().webpage_url,
+        dir.join("cache"),
+        &status,
+    );
+    assert!(matches!(
+        result,
+        Err(error::Error::SizeLimit { kept: Some(_), .. })
+    ));
+    assert_eq!(
+        fs::read_to_string(out_dir.join("video.mp4")).unwrap(),
+        "video\n"
+    );
+    assert!(!dir.join("cache").exists());
+}
+
+#[test]
+fn ipc_tokens_must_match_exactly() {
+    assert!(tokens_match(Some("secret"), Some("secret")));
+    assert!(!tokens_match(Some("secreT"), Some("secret")));
+    assert!(!tokens_match(Some("secret!"), Some("secret")));
+    assert!(!tokens_match(Some(""), Some("secret")));
+    assert!(!tokens_match(None, Some("secret")));
+}
+
+#[test]
+fn channel_doesnt_restart_stream_that_just_ended() {
+    let channel = Id::Yt {
+        yt_id: "@someone".to_string(),
+    };
+    let live_info = |id: &str, live_status: &str| -> YtLiveInfo {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": "A stream",
+            "is_live": false,
+            "was_live": true,
+            "webpage_url": format!("https://www.youtube.com/watch?v={id}"),
+            "uploader": "Someone",
+            "duration": 3600.0,
+            "live_status": live_status,
+            "release_timestamp": null,
+        }))
+        .unwrap()
+    };
+    let ended = live_info("dQw4w9WgXcQ", "post_live");
+    let video_id = Id::Yt {
+        yt_id: ended.id.clone(),
+    };
+    let mut inner = InnerSub::default();
+
+    // Its VOD can still be downloaded if it wasn't caught live.
+    assert!(inner.should_start(&channel, &video_id, &ended));
+
+    // Once it has been, the live page still pointing at it doesn't start it again,
+    // even after it's gone from `downloaded`, e.g. to be retried.
+    inner.last_video.insert(channel.clone(), video_id.clone());
+    assert!(!inner.should_start(&channel, &video_id, &ended));
+
+    // The channel's next stream is started.
+    let next = live_info("oHg5SJYRHA0", "is_live");
+    let next_id = Id::Yt {
+        yt_id: next.id.clone(),
+    };
+    assert!(inner.should_start(&channel, &next_id, &next));
+}
+
+/// Wait up to a few seconds for `f` to return something.
+fn wait_for<T>(mut f: impl FnMut() -> Option<T>) -> T {
+    let started = Instant::now();
+    loop {
+        if let Some(x) = f() {
+            return x;
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "timed out");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Whether process `pid` has exited, even if it hasn't been waited for yet.
+fn exited(pid: u32) -> bool {
+    let Ok(stat) = fs::read_to_string(format!("/proc/{pid}/stat")) else {
+        return true;
+    };
+    stat.rsplit_once(") ")
+        .is_some_and(|(_, rest)| rest.starts_with('Z'))
+}
+
+#[test]
+fn stopping_download_stops_chat() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("chat-stop");
+    // yt-dlp ignores the signal, so the chat only stops if it's signalled itself.
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"case "$args" in *--skip-download*) exec sleep 30 ;; esac
+trap '' TERM
+exec sleep 30"#,
+    );
+    let options = DlOptions {
+        out_dir: dir.join("out"),
+        download_chat: true,
+        ..Default::default()
+    };
+    let status = Arc::new(DlStatus::default());
+    let thread = std::thread::spawn({
+        let status = status.clone();
+        move || {
+            dl(
+                &yt_dlp,
+                &options,
+                &info(),
+                &info().webpage_url,
+                dir.join("cache"),
+                &status,
+            )
+        }
+    });
+
+    let chat = wait_for(|| status.children.lock().unwrap().first().copied());
+    status.signal(libc::SIGTERM);
+    wait_for(|| exited(chat).then_some(()));
+
+    status.signal(libc::SIGKILL);
+    assert!(thread.join().unwrap().is_err());
+    assert!(status.children.lock().unwrap().is_empty());
+}
+
+#[test]
+fn stopping_download_stops_pipe_command() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("pipe-stop");
+    let yt_dlp = mock_yt_dlp(&dir, "trap '' TERM\nexec sleep 30");
+    let options = DlOptions {
+        out_dir: dir.join("out"),
+        pipe_to: Some(vec!["sleep".to_string(), "30".to_string()]),
+        ..Default::default()
+    };
+    let status = Arc::new(DlStatus::default());
+    let thread = std::thread::spawn({
+        let status = status.clone();
+        move || {
+            dl(
+                &yt_dlp,
+                &options,
+                &info(),
+                &info().webpage_url,
+                dir.join("cache"),
+                &status,
+            )
+        }
+    });
+
+    let pipe = wait_for(|| status.children.lock().unwrap().first().copied());
+    wait_for(|| (status.pid.load(Ordering::Relaxed) != 0).then_some(()));
+    status.signal(libc::SIGTERM);
+    wait_for(|| exited(pipe).then_some(()));
+
+    status.signal(libc::SIGKILL);
+    assert!(thread.join().unwrap().is_err());
+    assert!(status.children.lock().unwrap().is_empty());
+}
+
+#[test]
+fn dl_sanitizes_filenames() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("sanitize");
+    // Like yt-dlp with a title of `A stream: part 1|2?`.
+    let mut yt_dlp = mock_yt_dlp_named(
+        &dir,
+        r#"$(case "$args" in
+            *--restrict-filenames*--windows-filenames*) echo A_stream_-_part_1_2 ;;
+            *) echo 'A stream: part 1|2?' ;;
+        esac).mkv"#,
+        r#"echo video > "$out"; echo "$out" > "$filepath""#,
+    );
+    yt_dlp.restrict_filenames(true).windows_filenames(true);
+
+    let (result, out_dir) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    // Both the filename probe and the download itself.
+    let runs = fs::read_to_string(dir.join("yt-dlp.args")).unwrap();
+    let runs = runs.lines().collect::<Vec<_>>();
+    assert_eq!(runs.len(), 2);
+    assert!(runs[0].contains("[vdl-filename]"));
+    for args in runs {
+        assert!(args.contains("--restrict-filenames"));
+        assert!(args.contains("--windows-filenames"));
+    }
+    assert_eq!(
+        fs::read_dir(&out_dir)
+            .unwrap()
+            .map(|x| x.unwrap().file_name())
+            .collect::<Vec<_>>(),
+        ["A_stream_-_part_1_2.mkv"]
+    );
+}
+
+#[test]
+fn finished_download_without_progress_bar() {
+    let id = Id::Yt { yt_id: info().id };
+    let thread = std::thread::spawn(|| Ok(()));
+    wait_for(|| thread.is_finished().then_some(()));
+    let mut inner = InnerSub::default();
+    inner.watching.insert(
+        id.clone(),
+        Watching {
+            thread,
+            info: info(),
+            dl_dir: test_dir("no-progress-bar"),
+            status: Arc::default(),
+            progress: (0, Instant::now()),
+            timeout_from: None,
+            timed_out: None,
+        },
+    );
+
+    // It isn't `silent`, so it should have had one, but that's only logged.
+    finish_downloads(&mut inner, &mut HashMap::new(), false);
+    assert!(inner.watching.is_empty());
+    assert!(matches!(
+        inner.downloaded[&id].outcome,
+        Some(Outcome::Succeeded)
+    ));
+}
+
+#[test]
+fn dl_embeds_chapters_after_remuxing() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("chapters");
+    let mut yt_dlp =
+        mock_yt_dlp(&dir, r#"echo video > "$out"; echo "$out" > "$filepath""#);
+    yt_dlp.remux_video(Some("mkv")).embed_chapters(true);
+
+    let (result, _) = run_dl(&yt_dlp, &dir);
+    result.unwrap();
+    let runs = fs::read_to_string(dir.join("yt-dlp.args")).unwrap();
+    let download = runs.lines().last().unwrap();
+    let remux = download.find("--remux-video mkv").unwrap();
+    let chapters = download.find("--embed-chapters").unwrap();
+    assert!(remux < chapters);
+}
+
+#[test]
+fn reloaded_dir_only_affects_later_downloads() {
+    let _lock = MOCK_LOCK.lock().unwrap();
+    let dir = test_dir("reload-dir");
+    let yt_dlp = mock_yt_dlp(
+        &dir,
+        r#"touch "$0.started"
+while [ ! -e "$0.go" ]; do sleep 0.05; done
+echo video > "$out"; echo "$out" > "$filepath""#,
+    );
+    let config = |out: &str| Config {
+        dir: Some(dir.join(out)),
+        ..Default::default()
+    };
+    let mut inner = InnerSub::default();
+    inner.apply_config(&config("before"));
+    let id = Id::Yt {
+        yt_id: "Someone".to_string(),
+    };
+    let options = inner.dl_options_for(&id, None);
+
+    let download = {
+        let (yt_dlp, dir) = (yt_dlp.clone(), dir.clone());
+        std::thread::spawn(move || {
+            dl(
+                &yt_dlp,
+                &options,
+                &info(),
+                &info().webpage_url,
+                dir.join("cache"),
+                &DlStatus::default(),
+            )
+        })
+    };
+    wait_for(|| dir.join("yt-dlp.started").exists().then_some(()));
+    inner.apply_config(&config("after"));
+    fs::write(dir.join("yt-dlp.go"), "").unwrap();
+
+    download.join().unwrap().unwrap();
+    assert!(dir.join("before/video.mkv").exists());
+    assert!(!dir.join("after").exists());
+    assert_eq!(inner.dl_options.out_dir, dir.join("after"));
+}
+
+#[test]
+fn log_keeps_levels() {
+    log!(warn, "log_keeps_levels {}", 1);
+    log!("log_keeps_levels {}", 2);
+    let records: Vec<_> = log::tail(usize::MAX)
+        .into_iter()
+        .filter(|x| x.line.starts_with("log_keeps_levels"))
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].level, log::Level::Warn);
+    assert_eq!(records[0].to_string(), "Warning: log_keeps_levels 1");
+    assert_eq!(records[1].level, log::Level::Info);
+    assert_eq!(records[1].to_string(), "log_keeps_levels 2");
+}
+
+#[test]
+fn finished_downloads_stopped_early_are_warnings() {
+    let mut inner = InnerSub::default();
+    let errors = [
+        error::Error::Killed {
+            signal: libc::SIGKILL,
+        },
+        error::Error::SizeLimit {
+            limit: "1M".to_string(),
+            kept:  None,
+        },
+    ];
+    for (i, error) in errors.into_iter().enumerate() {
+        let thread = std::thread::spawn(move || Err(error.into()));
+        wait_for(|| thread.is_finished().then_some(()));
+        let yt_id = format!("stopped-early-{i}");
+        inner.watching.insert(
+            Id::Yt {
+                yt_id: yt_id.clone(),
+            },
+            Watching {
+                thread,
+                info: Info {
+                    title: yt_id.clone(),
+                    ..info()
+                },
+                dl_dir: test_dir(&yt_id),
+                status: Arc::default(),
+                progress: (0, Instant::now()),
+                timeout_from: None,
+                timed_out: None,
+            },
+        );
+    }
+
+    finish_downloads(&mut inner, &mut HashMap::new(), true);
+    let records: Vec<_> = log::tail(usize::MAX)
+        .into_iter()
+        .filter(|x| x.line.contains("\"stopped-early-"))
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|x| x.level == log::Level::Warn));
+    let killed = records
+        .iter()
+        .find(|x| x.line.contains("stopped-early-0"))
+        .unwrap();
+    assert!(killed.line.contains("vdl retry"));
+    let size_limited = records
+        .iter()
+        .find(|x| x.line.contains("stopped-early-1"))
+        .unwrap();
+    assert!(size_limited.line.contains("size limit of 1M"));
+}
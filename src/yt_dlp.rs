@@ -1,23 +1,177 @@
+use crate::log::log;
 use eyre::eyre;
 use serde::Deserialize;
 use std::fs::{self, File, Permissions};
 use std::io::{Read, Write};
 use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 const NAME: &'static str = env!("CARGO_PKG_NAME");
+/// Use the yt-dlp at this path instead of downloading one.
+const EXE_ENV: &str = "VDL_YT_DLP_PATH";
+/// The yt-dlp release asset to download, if the config doesn't pick another.
+pub const DEFAULT_ASSET: &str = "yt-dlp_linux";
+/// The default User-Agent for requests to GitHub.
+pub const USER_AGENT: &str = "VDL via ureq";
+
+/// Browsers yt-dlp can load cookies from.
+const COOKIE_BROWSERS: &[&str] = &[
+    "brave", "chrome", "chromium", "edge", "firefox", "opera", "safari", "vivaldi",
+    "whale",
+];
+/// Keyrings yt-dlp can decrypt Chromium cookies with.
+const COOKIE_KEYRINGS: &[&str] = &[
+    "basictext",
+    "gnomekeyring",
+    "kwallet",
+    "kwallet5",
+    "kwallet6",
+];
+
+/// Check a `--cookies-from-browser` value of the form
+/// `BROWSER[+KEYRING][:PROFILE][::CONTAINER]`.
+pub fn check_cookies_from_browser(spec: &str) -> eyre::Result<()> {
+    let (browser, container) = match spec.split_once("::") {
+        Some((browser, container)) => (browser, Some(container)),
+        None => (spec, None),
+    };
+    let (browser, profile) = match browser.split_once(':') {
+        Some((browser, profile)) => (browser, Some(profile)),
+        None => (browser, None),
+    };
+    let (browser, keyring) = match browser.split_once('+') {
+        Some((browser, keyring)) => (browser, Some(keyring)),
+        None => (browser, None),
+    };
+
+    if !COOKIE_BROWSERS.contains(&browser.to_lowercase().as_str()) {
+        return Err(eyre!(
+            "unknown browser {browser:?}, expected one of: {}",
+            COOKIE_BROWSERS.join(", ")
+        ));
+    }
+    if let Some(keyring) = keyring
+        && !COOKIE_KEYRINGS.contains(&keyring.to_lowercase().as_str())
+    {
+        return Err(eyre!(
+            "unknown keyring {keyring:?}, expected one of: {}",
+            COOKIE_KEYRINGS.join(", ")
+        ));
+    }
+    if profile.is_some_and(str::is_empty) {
+        return Err(eyre!("the profile after ':' is empty"));
+    }
+    if container.is_some_and(str::is_empty) {
+        return Err(eyre!("the container after '::' is empty"));
+    }
+    Ok(())
+}
+
+/// The container a `--remux-video` value remuxes `source` into. The value is either a
+/// container, or rules like `mov>mp4/mkv`, where the first rule for `source` wins and
+/// a bare container is for anything else.
+pub fn remux_target<'a>(remux_video: &'a str, source: &str) -> Option<&'a str> {
+    remux_video
+        .split('/')
+        .find_map(|rule| match rule.split_once('>') {
+            Some((from, to)) => {
+                from.trim().eq_ignore_ascii_case(source).then(|| to.trim())
+            }
+            None => Some(rule.trim()),
+        })
+}
+
+/// Check a `--remux-video` value of the form `remux_target` understands.
+pub fn check_remux_video(remux_video: &str) -> eyre::Result<()> {
+    let is_container =
+        |x: &str| !x.is_empty() && x.chars().all(|c| c.is_ascii_alphanumeric());
+    for rule in remux_video.split('/') {
+        let valid = match rule.split_once('>') {
+            Some((from, to)) => is_container(from.trim()) && is_container(to.trim()),
+            None => is_container(rule.trim()),
+        };
+        if !valid {
+            return Err(eyre!(
+                "{rule:?} isn't a container or a rule like \"mov>mp4\""
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parse a rate like yt-dlp's `--limit-rate`, e.g. `50K` or `4.2M`, into bytes per
+/// second.
+pub fn parse_rate(rate: &str) -> eyre::Result<u64> {
+    parse_size(rate).map_err(|_| eyre!("{rate:?} isn't a rate like \"10M\""))
+}
+
+/// Parse a size like yt-dlp's `--max-filesize`, e.g. `50G`, into bytes.
+pub fn parse_size(size: &str) -> eyre::Result<u64> {
+    let invalid = || eyre!("{size:?} isn't a size like \"50G\"");
+    let size = size.trim();
+    let (number, multiplier) = match size.char_indices().last().ok_or_else(invalid)? {
+        (i, 'k' | 'K') => (&size[..i], 1_u64 << 10),
+        (i, 'm' | 'M') => (&size[..i], 1 << 20),
+        (i, 'g' | 'G') => (&size[..i], 1 << 30),
+        (i, 't' | 'T') => (&size[..i], 1 << 40),
+        _ => (size, 1),
+    };
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    if !number.is_finite() || number <= 0.0 {
+        return Err(invalid());
+    }
+    Ok((number * multiplier as f64) as u64)
+}
 
 #[derive(Clone, Debug)]
 pub struct YtDlp {
     pub live_from_start:      bool,
     pub embed_metadata:       bool,
     pub embed_thumbnail:      bool,
+    pub embed_chapters:       bool,
+    pub embed_info_json:      bool,
     pub no_progress:          bool,
+    pub keep_video:           bool,
+    pub geo_bypass:           bool,
+    pub restrict_filenames:   bool,
+    pub windows_filenames:    bool,
+    pub write_description:    bool,
+    pub write_thumbnail:      bool,
+    pub no_mtime:             bool,
+    pub force_ipv4:           bool,
+    pub force_ipv6:           bool,
     pub concurrent_fragments: Option<u8>,
     pub playlist_items:       Option<u64>,
+    pub min_duration:         Option<u64>,
+    pub min_height:           Option<u32>,
+    pub max_duration:         Option<u64>,
     pub remux_video:          Option<String>,
     pub cookies_from_browser: Option<String>,
+    pub cookies:              Option<PathBuf>,
+    pub geo_bypass_country:   Option<String>,
+    pub match_filter:         Option<String>,
+    pub sleep_requests:       Option<f32>,
+    pub sub_langs:            Option<String>,
+    pub sleep_interval:       Option<f32>,
+    pub max_sleep_interval:   Option<f32>,
+    pub throttled_rate:       Option<String>,
+    /// In bytes per second.
+    pub limit_rate:           Option<u64>,
+    pub max_filesize:         Option<String>,
+    pub age_limit:            Option<u32>,
+    pub buffer_size:          Option<String>,
+    pub http_chunk_size:      Option<String>,
+    pub download_archive:     Option<PathBuf>,
+    pub temp_path:            Option<PathBuf>,
+    pub home_path:            Option<PathBuf>,
+    pub wait_for_video:       Option<String>,
+    pub format_sort:          Option<String>,
+    pub merge_output_format:  Option<String>,
+    pub source_address:       Option<String>,
+    pub config_location:      Option<PathBuf>,
+    pub extra_args:           Vec<String>,
+    pub exe:                  Option<PathBuf>,
 }
 impl Default for YtDlp {
     fn default() -> Self {
@@ -25,24 +179,61 @@ impl Default for YtDlp {
             live_from_start:      false,
             embed_metadata:       true,
             embed_thumbnail:      true,
+            embed_chapters:       false,
+            embed_info_json:      false,
             no_progress:          true,
+            keep_video:           false,
+            geo_bypass:           false,
+            restrict_filenames:   false,
+            windows_filenames:    false,
+            write_description:    false,
+            write_thumbnail:      false,
+            no_mtime:             false,
+            force_ipv4:           false,
+            force_ipv6:           false,
+            geo_bypass_country:   None,
+            match_filter:         None,
+            sleep_requests:       None,
+            sub_langs:            None,
+            sleep_interval:       None,
+            max_sleep_interval:   None,
+            throttled_rate:       None,
+            limit_rate:           None,
+            max_filesize:         None,
+            age_limit:            None,
+            buffer_size:          None,
+            http_chunk_size:      None,
+            download_archive:     None,
+            temp_path:            None,
+            home_path:            None,
+            wait_for_video:       None,
+            format_sort:          None,
+            merge_output_format:  None,
+            source_address:       None,
+            config_location:      None,
+            extra_args:           Vec::new(),
+            exe:                  None,
             concurrent_fragments: None,
             cookies_from_browser: None,
+            cookies:              None,
             remux_video:          None,
             playlist_items:       None,
+            min_duration:         None,
+            min_height:           None,
+            max_duration:         None,
         }
     }
 }
 
 impl YtDlp {
     /// Get the lastest tag
-    pub fn get_latest_tag() -> eyre::Result<String> {
+    pub fn get_latest_tag(agent: &ureq::Agent) -> eyre::Result<String> {
         #[derive(Debug, Clone, Deserialize)]
         struct Tag {
             name: String,
         }
-        let body: String = ureq::get("https://api.github.com/repos/yt-dlp/yt-dlp/tags")
-            .header("User-Agent", "VDL via ureq")
+        let body: String = agent
+            .get("https://api.github.com/repos/yt-dlp/yt-dlp/tags")
             .call()?
             .body_mut()
             .read_to_string()?;
@@ -54,42 +245,122 @@ impl YtDlp {
         Ok(latest)
     }
 
-    pub fn download_latest() -> eyre::Result<()> {
+    /// Whether yt-dlp is `$VDL_YT_DLP_PATH`, which someone else keeps up to date.
+    pub fn is_external() -> bool {
+        std::env::var_os(EXE_ENV).is_some()
+    }
+
+    /// Check that the release `tag` has an asset called `asset`.
+    fn check_asset(agent: &ureq::Agent, tag: &str, asset: &str) -> eyre::Result<()> {
+        #[derive(Debug, Deserialize)]
+        struct Release {
+            assets: Vec<Asset>,
+        }
+        #[derive(Debug, Deserialize)]
+        struct Asset {
+            name: String,
+        }
+        let body = agent
+            .get(format!(
+                "https://api.github.com/repos/yt-dlp/yt-dlp/releases/tags/{tag}"
+            ))
+            .call()?
+            .body_mut()
+            .read_to_string()?;
+        let release = serde_json::de::from_str::<Release>(&body)?;
+        if !release.assets.iter().any(|x| x.name == asset) {
+            let names = release.assets.iter().map(|x| x.name.as_str());
+            return Err(eyre!(
+                "yt-dlp {tag} has no asset {asset:?}, only: {}",
+                names.collect::<Vec<_>>().join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Make sure the latest yt-dlp is installed, or download it again anyway if
+    /// `force` is set. `asset` is the release asset to download, `DEFAULT_ASSET` if
+    /// it's `None`. `agent` sets the User-Agent and timeouts for the requests to
+    /// GitHub.
+    pub fn download_latest(
+        agent: &ureq::Agent,
+        asset: Option<&str>,
+        force: bool,
+    ) -> eyre::Result<()> {
+        if Self::is_external() {
+            return Ok(());
+        }
         let yt_dlp_exe = Self::exe_path();
-        let latest = Self::get_latest_tag()?;
+        let latest = Self::get_latest_tag(agent)?;
         let latest = latest.trim();
 
-        if yt_dlp_exe.exists() {
-            // check the version against the latest to see if we need to update it.
-            let output = Command::new(&yt_dlp_exe).arg("--version").output()?;
-            let stdout = String::from_utf8(output.stdout)?;
-            let version = stdout.trim();
-            if version == latest {
-                return Ok(());
-            }
-            fs::remove_file(&yt_dlp_exe)?;
+        // check the version against the latest to see if we need to update it. One
+        // that can't even run, e.g. a glibc build on musl, counts as not installed.
+        if !force
+            && Self::installed_version().ok().flatten().as_deref() == Some(latest)
+            && Self::installed_asset() == asset.unwrap_or(DEFAULT_ASSET)
+        {
+            return Ok(());
         }
 
+        // The default is always there, so save the request.
+        let asset = match asset {
+            Some(asset) => {
+                Self::check_asset(agent, latest, asset)?;
+                asset
+            }
+            None => DEFAULT_ASSET,
+        };
         let url = format!(
-            "https://github.com/yt-dlp/yt-dlp/releases/download/{latest}/yt-dlp_linux"
+            "https://github.com/yt-dlp/yt-dlp/releases/download/{latest}/{asset}"
         );
-        eprintln!("Downloading yt-dlp {latest} from {url:?}");
-        let mut f = File::create(&yt_dlp_exe)?;
-        let mut body = ureq::get(&url)
-            .header("User-Agent", "VDL via ureq")
-            .call()?
-            .into_body();
+        log!("Downloading yt-dlp {latest} from {url:?}");
+        // Downloads can be running, so replace it in one go rather than having them
+        // run a half written file.
+        let part = yt_dlp_exe.with_extension("part");
+        let mut f = File::create(&part)?;
+        let mut body = agent.get(&url).call()?.into_body();
         let mut body = body.as_reader();
         let mut buf = vec![];
         body.read_to_end(&mut buf)?;
         f.write_all(&buf)?;
         std::mem::drop(f);
-        fs::set_permissions(&yt_dlp_exe, Permissions::from_mode(0o755))?;
-        eprintln!("Done downloading the latest yt-dlp!");
+        fs::set_permissions(&part, Permissions::from_mode(0o755))?;
+        fs::rename(&part, &yt_dlp_exe)?;
+        fs::write(Self::asset_path(), asset)?;
+        log!("Done downloading the latest yt-dlp!");
         Ok(())
     }
 
+    /// The version of the yt-dlp at `exe_path()`, or `None` if it isn't there.
+    pub fn installed_version() -> eyre::Result<Option<String>> {
+        let yt_dlp_exe = Self::exe_path();
+        if !yt_dlp_exe.exists() {
+            return Ok(None);
+        }
+        let output = Command::new(&yt_dlp_exe).arg("--version").output()?;
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(Some(stdout.trim().to_string()))
+    }
+
+    /// The release asset the yt-dlp at `exe_path()` was downloaded from. Ones
+    /// downloaded before that was recorded are `DEFAULT_ASSET`.
+    fn installed_asset() -> String {
+        fs::read_to_string(Self::asset_path())
+            .map(|x| x.trim().to_string())
+            .unwrap_or_else(|_| DEFAULT_ASSET.to_string())
+    }
+
+    /// Where the name of the installed release asset is recorded.
+    fn asset_path() -> PathBuf {
+        Self::exe_path().with_extension("asset")
+    }
+
+    /// Where yt-dlp is installed, or `$VDL_YT_DLP_PATH` if it's set.
     pub fn exe_path() -> PathBuf {
+        if let Some(path) = std::env::var_os(EXE_ENV) {
+            return PathBuf::from(path);
+        }
         let state_dir = dirs::state_dir().expect("state dir").join(NAME);
         state_dir.join("yt_dlp")
     }
@@ -99,11 +370,30 @@ impl YtDlp {
     }
 
     pub fn command_with_args(&self) -> Command {
-        let mut c = Self::command();
+        let mut c = Command::new(self.exe.clone().unwrap_or_else(Self::exe_path));
         c.args(self.args());
         c
     }
 
+    /// The `--download-archive` arguments. These aren't in `command_with_args`, since
+    /// yt-dlp won't extract anything it finds in the archive, so they're only for the
+    /// downloads themselves.
+    pub fn archive_args(&self) -> Vec<String> {
+        match &self.download_archive {
+            Some(path) => vec![
+                "--download-archive".to_string(),
+                path.to_string_lossy().into_owned(),
+            ],
+            None => vec![],
+        }
+    }
+
+    /// Run the yt-dlp at `path` instead of the one at `exe_path()`.
+    pub fn with_exe(&mut self, path: Option<&Path>) -> &mut Self {
+        self.exe = path.map(Path::to_path_buf);
+        self
+    }
+
     pub fn concurrent_fragments(&mut self, n: Option<u8>) -> &mut Self {
         self.concurrent_fragments = n;
         self
@@ -116,6 +406,11 @@ impl YtDlp {
         self.cookies_from_browser = browser.map(str::to_string);
         self
     }
+    /// A Netscape cookies file to use instead of `cookies_from_browser`.
+    pub fn cookies(&mut self, file: Option<&Path>) -> &mut Self {
+        self.cookies = file.map(Path::to_path_buf);
+        self
+    }
     pub fn live_from_start(&mut self, enabled: bool) -> &mut Self {
         self.live_from_start = enabled;
         self
@@ -128,6 +423,17 @@ impl YtDlp {
         self.embed_thumbnail = enabled;
         self
     }
+    /// Embed chapter markers, into the remuxed file with `remux_video`. They're added
+    /// to by SponsorBlock's `--sponsorblock-mark`, if it's used.
+    pub fn embed_chapters(&mut self, enabled: bool) -> &mut Self {
+        self.embed_chapters = enabled;
+        self
+    }
+    /// Attach the info JSON to the video. This only works for mkv and mka files.
+    pub fn embed_info_json(&mut self, enabled: bool) -> &mut Self {
+        self.embed_info_json = enabled;
+        self
+    }
     pub fn no_progress(&mut self, no_progress: bool) -> &mut Self {
         self.no_progress = no_progress;
         self
@@ -136,9 +442,231 @@ impl YtDlp {
         self.remux_video = format.map(str::to_string);
         self
     }
+    pub fn keep_video(&mut self, enabled: bool) -> &mut Self {
+        self.keep_video = enabled;
+        self
+    }
+    pub fn geo_bypass(&mut self, enabled: bool) -> &mut Self {
+        self.geo_bypass = enabled;
+        self
+    }
+    pub fn geo_bypass_country(&mut self, country: Option<&str>) -> &mut Self {
+        self.geo_bypass_country = country.map(str::to_string);
+        self
+    }
+    /// Restrict file names to ASCII characters and avoid "&" and spaces.
+    pub fn restrict_filenames(&mut self, enabled: bool) -> &mut Self {
+        self.restrict_filenames = enabled;
+        self
+    }
+    /// Force file names to be Windows-compatible.
+    pub fn windows_filenames(&mut self, enabled: bool) -> &mut Self {
+        self.windows_filenames = enabled;
+        self
+    }
+    /// Write the video's description to a `.description` file next to it.
+    pub fn write_description(&mut self, enabled: bool) -> &mut Self {
+        self.write_description = enabled;
+        self
+    }
+    /// Save the thumbnail as a `.jpg` next to the video, which media servers use as the
+    /// poster. This is independent of `embed_thumbnail`.
+    pub fn write_thumbnail(&mut self, enabled: bool) -> &mut Self {
+        self.write_thumbnail = enabled;
+        self
+    }
+    /// Download the subtitles in these languages and embed them, e.g. `en.*,ja`. The
+    /// value is passed to `--sub-langs` as-is.
+    pub fn sub_langs(&mut self, langs: Option<&str>) -> &mut Self {
+        self.sub_langs = langs.map(str::to_string);
+        self
+    }
+    /// Give downloaded files the current time as their modification time, instead of
+    /// the upload date.
+    pub fn no_mtime(&mut self, enabled: bool) -> &mut Self {
+        self.no_mtime = enabled;
+        self
+    }
+    /// Make all connections over IPv4.
+    pub fn force_ipv4(&mut self, enabled: bool) -> &mut Self {
+        self.force_ipv4 = enabled;
+        self
+    }
+    /// Make all connections over IPv6.
+    pub fn force_ipv6(&mut self, enabled: bool) -> &mut Self {
+        self.force_ipv6 = enabled;
+        self
+    }
+    /// Bind connections to this local IP address.
+    pub fn source_address(&mut self, address: Option<&str>) -> &mut Self {
+        self.source_address = address.map(str::to_string);
+        self
+    }
+    /// Skip videos shorter than `secs`. Videos with an unknown duration (e.g. live
+    /// streams) aren't skipped.
+    pub fn min_duration(&mut self, secs: Option<u64>) -> &mut Self {
+        self.min_duration = secs;
+        self
+    }
+    /// Skip videos longer than `secs`. Videos with an unknown duration (e.g. live
+    /// streams) aren't skipped.
+    pub fn max_duration(&mut self, secs: Option<u64>) -> &mut Self {
+        self.max_duration = secs;
+        self
+    }
+
+    /// Skip videos less than `height` pixels tall, and audio-only ones. Live streams'
+    /// formats often aren't known yet, so they're checked again with `height_allowed`
+    /// once they're downloaded.
+    pub fn min_height(&mut self, height: Option<u32>) -> &mut Self {
+        self.min_height = height;
+        self
+    }
+
+    /// Only download videos matching a yt-dlp filter expression, e.g.
+    /// `title ~= (?i)tournament`. This is combined with the duration filters.
+    pub fn match_filter(&mut self, filter: Option<&str>) -> &mut Self {
+        self.match_filter = filter.map(str::to_string);
+        self
+    }
+
+    /// Seconds to sleep between requests during data extraction.
+    pub fn sleep_requests(&mut self, secs: Option<f32>) -> &mut Self {
+        self.sleep_requests = secs;
+        self
+    }
+
+    /// Seconds to sleep before each download, or the least to sleep if there's a
+    /// `max_sleep_interval`.
+    pub fn sleep_interval(&mut self, secs: Option<f32>) -> &mut Self {
+        self.sleep_interval = secs;
+        self
+    }
+
+    /// Sleep a random amount of time between `sleep_interval` and this before each
+    /// download.
+    pub fn max_sleep_interval(&mut self, secs: Option<f32>) -> &mut Self {
+        self.max_sleep_interval = secs;
+        self
+    }
+
+    /// Re-extract the video when the download rate drops below `rate`, e.g. `100K`.
+    pub fn throttled_rate(&mut self, rate: Option<&str>) -> &mut Self {
+        self.throttled_rate = rate.map(str::to_string);
+        self
+    }
+
+    /// Download at most `rate` bytes per second.
+    pub fn limit_rate(&mut self, rate: Option<u64>) -> &mut Self {
+        self.limit_rate = rate;
+        self
+    }
+
+    /// Wait for scheduled streams to start, retrying every `interval` seconds, as
+    /// `MIN[-MAX]`.
+    ///
+    /// Info requests (`-J`) would block too, so this should only be set for downloads.
+    pub fn wait_for_video(&mut self, interval: Option<&str>) -> &mut Self {
+        self.wait_for_video = interval.map(str::to_string);
+        self
+    }
+
+    /// Sort the formats by these fields to pick the best one, e.g. `res:1080,fps,vcodec`.
+    pub fn format_sort(&mut self, sort: Option<&str>) -> &mut Self {
+        self.format_sort = sort.map(str::to_string);
+        self
+    }
+
+    /// The container to use when separate video and audio formats are merged, e.g.
+    /// `mp4`. Unlike `remux_video`, this doesn't affect downloads that weren't merged,
+    /// and remuxing happens after merging.
+    pub fn merge_output_format(&mut self, format: Option<&str>) -> &mut Self {
+        self.merge_output_format = format.map(str::to_string);
+        self
+    }
+
+    /// Record downloaded videos in the file at `path`, and skip the ones in it.
+    pub fn download_archive(&mut self, path: Option<&Path>) -> &mut Self {
+        self.download_archive = path.map(Path::to_path_buf);
+        self
+    }
+
+    /// Have yt-dlp download into `path`, and move finished files out of it itself.
+    pub fn temp_path(&mut self, path: Option<&Path>) -> &mut Self {
+        self.temp_path = path.map(Path::to_path_buf);
+        self
+    }
+
+    /// The directory relative output paths are in.
+    pub fn home_path(&mut self, path: Option<&Path>) -> &mut Self {
+        self.home_path = path.map(Path::to_path_buf);
+        self
+    }
+
+    /// Load yt-dlp options from the config file at `path`.
+    pub fn config_location(&mut self, path: Option<&Path>) -> &mut Self {
+        self.config_location = path.map(Path::to_path_buf);
+        self
+    }
+
+    /// Arguments passed to yt-dlp verbatim, after the ones set by the other options.
+    pub fn extra_args(&mut self, args: &[String]) -> &mut Self {
+        self.extra_args = args.to_vec();
+        self
+    }
+
+    /// The download buffer size, e.g. `16K`.
+    pub fn buffer_size(&mut self, size: Option<&str>) -> &mut Self {
+        self.buffer_size = size.map(str::to_string);
+        self
+    }
+
+    /// Download HTTP formats in chunks of `size`, e.g. `10M`.
+    pub fn http_chunk_size(&mut self, size: Option<&str>) -> &mut Self {
+        self.http_chunk_size = size.map(str::to_string);
+        self
+    }
+
+    /// Stop downloading when the file gets larger than `size`, e.g. `50G`.
+    pub fn max_filesize(&mut self, size: Option<&str>) -> &mut Self {
+        self.max_filesize = size.map(str::to_string);
+        self
+    }
+
+    /// Skip videos that aren't suitable for someone `years` old.
+    pub fn age_limit(&mut self, years: Option<u32>) -> &mut Self {
+        self.age_limit = years;
+        self
+    }
+
+    /// Whether a video with the given age rating is allowed by `age_limit`. Videos
+    /// without a rating are always allowed.
+    pub fn age_allowed(&self, age_limit: Option<u32>) -> bool {
+        match (self.age_limit, age_limit) {
+            (Some(max), Some(age)) => age <= max,
+            _ => true,
+        }
+    }
+
+    /// Whether a video of `duration` seconds is within the configured bounds.
+    pub fn duration_in_bounds(&self, duration: f64) -> bool {
+        self.min_duration.is_none_or(|min| duration >= min as f64)
+            && self.max_duration.is_none_or(|max| duration <= max as f64)
+    }
+
+    /// Whether a video that's `height` pixels tall, or audio-only if it's `None`, is
+    /// allowed by `min_height`.
+    pub fn height_allowed(&self, height: Option<u32>) -> bool {
+        self.min_height
+            .is_none_or(|min| height.is_some_and(|height| height >= min))
+    }
 
     fn args(&self) -> Vec<String> {
         let mut args = vec![];
+        if let Some(path) = &self.config_location {
+            args.push("--config-locations".to_string());
+            args.push(path.to_string_lossy().into_owned());
+        }
         if self.live_from_start {
             args.push("--live-from-start".to_string());
         }
@@ -148,6 +676,9 @@ impl YtDlp {
         if self.embed_thumbnail {
             args.push("--embed-thumbnail".to_string());
         }
+        if self.embed_info_json {
+            args.push("--embed-info-json".to_string());
+        }
         if self.no_progress {
             args.push("--no-progress".to_string());
         }
@@ -159,7 +690,10 @@ impl YtDlp {
             args.push("--playlist-items".to_string());
             args.push(n.to_string());
         }
-        if let Some(browser) = &self.cookies_from_browser {
+        if let Some(file) = &self.cookies {
+            args.push("--cookies".to_string());
+            args.push(file.to_string_lossy().into_owned());
+        } else if let Some(browser) = &self.cookies_from_browser {
             args.push("--cookies-from-browser".to_string());
             args.push(browser.clone());
         }
@@ -167,6 +701,128 @@ impl YtDlp {
             args.push("--remux-video".to_string());
             args.push(format.clone());
         }
+        // After remuxing, so they're embedded in what it was remuxed to.
+        if self.embed_chapters {
+            args.push("--embed-chapters".to_string());
+        }
+        if self.keep_video {
+            args.push("--keep-video".to_string());
+        }
+        if self.geo_bypass {
+            args.push("--geo-bypass".to_string());
+        }
+        if self.no_mtime {
+            args.push("--no-mtime".to_string());
+        }
+        if self.force_ipv4 {
+            args.push("-4".to_string());
+        }
+        if self.force_ipv6 {
+            args.push("-6".to_string());
+        }
+        if let Some(address) = &self.source_address {
+            args.push("--source-address".to_string());
+            args.push(address.clone());
+        }
+        if let Some(country) = &self.geo_bypass_country {
+            args.push("--geo-bypass-country".to_string());
+            args.push(country.clone());
+        }
+        if let Some(secs) = self.sleep_requests {
+            args.push("--sleep-requests".to_string());
+            args.push(secs.to_string());
+        }
+        if let Some(secs) = self.sleep_interval {
+            args.push("--sleep-interval".to_string());
+            args.push(secs.to_string());
+        }
+        if let Some(secs) = self.max_sleep_interval {
+            args.push("--max-sleep-interval".to_string());
+            args.push(secs.to_string());
+        }
+        if let Some(rate) = &self.throttled_rate {
+            args.push("--throttled-rate".to_string());
+            args.push(rate.clone());
+        }
+        if let Some(rate) = self.limit_rate {
+            args.push("--limit-rate".to_string());
+            args.push(rate.to_string());
+        }
+        if let Some(format) = &self.merge_output_format {
+            args.push("--merge-output-format".to_string());
+            args.push(format.clone());
+        }
+        if let Some(sort) = &self.format_sort {
+            args.push("-S".to_string());
+            args.push(sort.clone());
+        }
+        if let Some(interval) = &self.wait_for_video {
+            args.push("--wait-for-video".to_string());
+            args.push(interval.clone());
+        }
+        if let Some(path) = &self.temp_path {
+            args.push("--paths".to_string());
+            args.push(format!("temp:{}", path.to_string_lossy()));
+        }
+        if let Some(path) = &self.home_path {
+            args.push("--paths".to_string());
+            args.push(format!("home:{}", path.to_string_lossy()));
+        }
+        if let Some(size) = &self.buffer_size {
+            args.push("--buffer-size".to_string());
+            args.push(size.clone());
+        }
+        if let Some(size) = &self.http_chunk_size {
+            args.push("--http-chunk-size".to_string());
+            args.push(size.clone());
+        }
+        if let Some(size) = &self.max_filesize {
+            args.push("--max-filesize".to_string());
+            args.push(size.clone());
+        }
+        if let Some(years) = self.age_limit {
+            args.push("--age-limit".to_string());
+            args.push(years.to_string());
+        }
+        if self.restrict_filenames {
+            args.push("--restrict-filenames".to_string());
+        }
+        if self.windows_filenames {
+            args.push("--windows-filenames".to_string());
+        }
+        if self.write_description {
+            args.push("--write-description".to_string());
+        }
+        if let Some(langs) = &self.sub_langs {
+            args.push("--write-subs".to_string());
+            args.push("--sub-langs".to_string());
+            args.push(langs.clone());
+            args.push("--embed-subs".to_string());
+        }
+        if self.write_thumbnail {
+            args.push("--write-thumbnail".to_string());
+            args.push("--convert-thumbnails".to_string());
+            args.push("jpg".to_string());
+        }
+        let mut filters = vec![];
+        if let Some(filter) = &self.match_filter {
+            filters.push(filter.clone());
+        }
+        if let Some(min) = self.min_duration {
+            filters.push(format!("duration >=? {min}"));
+        }
+        if let Some(max) = self.max_duration {
+            filters.push(format!("duration <=? {max}"));
+        }
+        if let Some(min) = self.min_height {
+            filters.push(format!("height >=? {min}"));
+        }
+        if !filters.is_empty() {
+            // Multiple `--match-filter`s are OR'd together, so they're joined into one.
+            args.push("--match-filter".to_string());
+            args.push(filters.join(" & "));
+        }
+        args.extend(self.extra_args.iter().cloned());
 
         args
     }